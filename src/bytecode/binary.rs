@@ -0,0 +1,225 @@
+//! Binary bytecode format written by `morango compile`.
+//!
+//! All integers are little-endian. The layout is:
+//!
+//! ```text
+//! magic "MORB", version: u8
+//! data_size: u16
+//! strings: u16 count, then for each string: u16 length + UTF-8 bytes
+//! params: u16 count, then for each param: u16 length + UTF-8 name, u16 address
+//! vars: u16 count, then for each variable: u16 length + UTF-8 name, u16 address
+//! instructions: u16 count, then for each instruction:
+//!     opcode: u8, args: u16 count + u16 values
+//! ```
+//!
+//! Instructions without arguments are stored with an argument count of zero.
+//! Programs with counts or lengths above `u16::MAX` cannot be encoded.
+
+use super::instruction::Instruction;
+use super::ByteCode;
+use crate::config::{OpCodes, Value};
+use crate::reader::Reader;
+
+const MAGIC: &[u8; 4] = b"MORB";
+const VERSION: u8 = 1;
+
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub fn encode(bytecode: &ByteCode) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+    write_u16(&mut out, fit(bytecode.data_size, "data size")?);
+    write_u16(&mut out, fit(bytecode.strings.len(), "string count")?);
+    for s in &bytecode.strings {
+        write_str(&mut out, s)?;
+    }
+    write_u16(&mut out, fit(bytecode.params.len(), "parameter count")?);
+    for (name, address) in &bytecode.params {
+        write_str(&mut out, name)?;
+        write_u16(&mut out, *address);
+    }
+    write_u16(&mut out, fit(bytecode.vars.len(), "variable count")?);
+    for (name, address) in &bytecode.vars {
+        write_str(&mut out, name)?;
+        write_u16(&mut out, *address);
+    }
+    write_u16(
+        &mut out,
+        fit(bytecode.instructions.len(), "instruction count")?,
+    );
+    for instruction in &bytecode.instructions {
+        out.push(instruction.opcode.map_or(0, |opcode| opcode as u8));
+        let args = instruction.args.as_deref().unwrap_or(&[]);
+        write_u16(&mut out, fit(args.len(), "argument count")?);
+        for arg in args {
+            write_u16(&mut out, *arg);
+        }
+    }
+    Ok(out)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<ByteCode, String> {
+    let mut reader = Reader::new(bytes, "Invalid bytecode");
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err("Invalid bytecode: missing MORB header".to_string());
+    }
+    let version = reader.read_u8()?;
+    if version != VERSION {
+        return Err(format!(
+            "Invalid bytecode: unsupported format version {}",
+            version
+        ));
+    }
+    let data_size = reader.read_u16()? as usize;
+    let mut strings = Vec::new();
+    for _ in 0..reader.read_u16()? {
+        strings.push(read_str(&mut reader)?);
+    }
+    let mut params = Vec::new();
+    for _ in 0..reader.read_u16()? {
+        let name = read_str(&mut reader)?;
+        params.push((name, reader.read_u16()?));
+    }
+    let mut vars = Vec::new();
+    for _ in 0..reader.read_u16()? {
+        let name = read_str(&mut reader)?;
+        vars.push((name, reader.read_u16()?));
+    }
+    let mut instructions = Vec::new();
+    for _ in 0..reader.read_u16()? {
+        let opcode = OpCodes::try_from(reader.read_u8()?).map_err(|e| reader.error(e))?;
+        let mut args = Vec::new();
+        for _ in 0..reader.read_u16()? {
+            args.push(reader.read_u16()?);
+        }
+        instructions.push(Instruction {
+            opcode: Some(opcode),
+            args: if args.is_empty() { None } else { Some(args) },
+        });
+    }
+    reader.finish()?;
+    Ok(ByteCode {
+        instructions,
+        data_size,
+        strings,
+        params,
+        vars,
+    })
+}
+
+fn write_u16(out: &mut Vec<u8>, v: Value) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) -> Result<(), String> {
+    write_u16(out, fit(s.len(), "string length")?);
+    out.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+
+/// Converts a count or length to the u16 stored in the file, refusing values
+/// that do not fit.
+fn fit(value: usize, what: &str) -> Result<u16, String> {
+    u16::try_from(value)
+        .map_err(|_| format!("Unable to encode bytecode: {} {} is too large", what, value))
+}
+
+fn read_str(reader: &mut Reader) -> Result<String, String> {
+    let len = reader.read_u16()? as usize;
+    reader.read_utf8(len, "string")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    fn transpile(code: &str) -> ByteCode {
+        let mut reader = BufReader::new(code.as_bytes());
+        ByteCode::do_transpile(&mut reader).unwrap()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let bytecode = transpile(concat!(
+            ".param n\n",
+            "PRINT_STR \"n is\"\n",
+            "READ_VAR n\n",
+            "&label\n",
+            "DUP\n",
+            "PRINT\n",
+            "LOAD_VAL 0\n",
+            "GOTO &label\n",
+            "JUMP &end\n",
+            "GOTO_IF_ZERO &label\n",
+            "SWITCH &label &end default &end\n",
+            "&end\n",
+            "RETURN_VALUE\n",
+        ));
+        let bytes = encode(&bytecode).unwrap();
+        assert!(is_binary(&bytes));
+        assert_eq!(decode(&bytes), Ok(bytecode));
+    }
+
+    #[test]
+    fn roundtrip_bitwise() {
+        let bytecode = transpile(concat!(
+            "LOAD_VAL 0xF0F0 | LOAD_VAL 0x0FF0 | AND\n",
+            "LOAD_VAL 1 | OR | LOAD_VAL 0xFF | XOR | NOT\n",
+            "LOAD_VAL 2 | SHL | LOAD_VAL 1 | SHR\n",
+            "RETURN_VALUE\n",
+        ));
+        assert_eq!(decode(&encode(&bytecode).unwrap()), Ok(bytecode));
+    }
+
+    #[test]
+    fn encode_too_large() {
+        let literal = "x".repeat(70_000);
+        let bytecode = transpile(&format!("PRINT_STR \"{}\"", literal));
+        assert_eq!(
+            encode(&bytecode),
+            Err("Unable to encode bytecode: string length 70000 is too large".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_bad_magic() {
+        assert_eq!(
+            decode(b"LOAD_VAL 1"),
+            Err("Invalid bytecode: missing MORB header".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_truncated() {
+        let bytes = encode(&transpile("LOAD_VAL 1\nRETURN_VALUE")).unwrap();
+        assert_eq!(
+            decode(&bytes[..bytes.len() - 1]),
+            Err("Invalid bytecode: unexpected end of data".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_unknown_opcode() {
+        let mut bytes = encode(&transpile("RETURN_VALUE")).unwrap();
+        let last = bytes.len() - 3;
+        bytes[last] = 0xFF;
+        assert_eq!(
+            decode(&bytes),
+            Err("Invalid bytecode: unknown opcode 0xFF".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_unsupported_version() {
+        let mut bytes = encode(&transpile("RETURN_VALUE")).unwrap();
+        bytes[MAGIC.len()] = 2;
+        assert_eq!(
+            decode(&bytes),
+            Err("Invalid bytecode: unsupported format version 2".to_string())
+        );
+    }
+}
@@ -1,14 +1,45 @@
+use super::macros::Macro;
 use crate::config::Value;
 use lazy_static::lazy_static;
 use regex::Regex;
 use std::collections::HashMap;
 
+/// A jump operand naming a label that was not declared yet. It is patched once
+/// the whole program has been transpiled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwardRef {
+    pub label: String,
+    pub instruction: Value,
+    pub arg: usize,
+    /// Where the reference appears, used in the error if the label is never
+    /// declared.
+    pub location: String,
+}
+
+/// A `.scope` block being transpiled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Scope {
+    /// Variables declared inside the block, released by `.endscope`.
+    names: Vec<String>,
+    /// Where the block starts, used in the error if it is never closed.
+    location: String,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Context {
     data: HashMap<String, Value>,
+    data_size: usize,
+    scopes: Vec<Scope>,
     labels: HashMap<String, Value>,
+    consts: HashMap<String, Value>,
+    macros: HashMap<String, Macro>,
+    expansions: usize,
+    forward_refs: Vec<ForwardRef>,
     args: Vec<String>,
+    strings: Vec<String>,
+    params: Vec<(String, Value)>,
     pub line_number: usize,
+    pub location: String,
     pub instruction_number: Value,
 }
 
@@ -16,9 +47,18 @@ impl Context {
     pub fn new() -> Context {
         Context {
             data: HashMap::new(),
+            data_size: 0,
+            scopes: Vec::new(),
             labels: HashMap::new(),
+            consts: HashMap::new(),
+            macros: HashMap::new(),
+            expansions: 0,
+            forward_refs: Vec::new(),
             args: Vec::new(),
+            strings: Vec::new(),
+            params: Vec::new(),
             line_number: 0,
+            location: String::new(),
             instruction_number: 0,
         }
     }
@@ -31,14 +71,66 @@ impl Context {
         LABEL_RE.is_match(label)
     }
 
+    pub fn is_var_name(name: &str) -> bool {
+        lazy_static! {
+            static ref VAR_RE: Regex =
+                Regex::new(r"^[a-zA-Z_][a-zA-Z0-9_]*").expect("Invalid regex");
+        }
+        VAR_RE.is_match(name)
+    }
+
+    pub fn is_const_name(name: &str) -> bool {
+        lazy_static! {
+            static ref CONST_RE: Regex =
+                Regex::new(r"^[a-zA-Z_][a-zA-Z0-9_]*$").expect("Invalid regex");
+        }
+        CONST_RE.is_match(name)
+    }
+
     pub fn has_var(&self, name: &str) -> bool {
         self.data.contains_key(name)
     }
 
-    pub fn add_var(&mut self, name: &str) -> Value {
-        let address = self.data.len() as Value;
+    /// Declares a variable at the lowest free address. Scoped variables are
+    /// always the last ones declared, so the visible variables occupy the
+    /// addresses below `data.len()`.
+    pub fn add_var(&mut self, name: &str) -> Result<Value, String> {
+        let address = Self::next_index(self.data.len(), "variables")?;
         self.data.insert(name.to_string(), address);
-        address
+        self.data_size = self.data_size.max(self.data.len());
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.names.push(name.to_string());
+        }
+        Ok(address)
+    }
+
+    pub fn open_scope(&mut self) {
+        self.scopes.push(Scope {
+            names: Vec::new(),
+            location: self.location.clone(),
+        });
+    }
+
+    /// Forgets the variables declared in the innermost scope, making their
+    /// addresses available to later declarations.
+    pub fn close_scope(&mut self) -> Result<(), String> {
+        let scope = match self.scopes.pop() {
+            Some(scope) => scope,
+            None => return Err(".endscope without .scope".to_string()),
+        };
+        for name in scope.names {
+            self.data.remove(&name);
+        }
+        Ok(())
+    }
+
+    pub fn in_scope(&self) -> bool {
+        !self.scopes.is_empty()
+    }
+
+    /// Returns where the innermost scope that is still open starts.
+    pub fn open_scope_location(&self) -> Option<&str> {
+        self.scopes.last().map(|scope| scope.location.as_str())
     }
 
     pub fn has_label(&self, name: &str) -> bool {
@@ -50,11 +142,75 @@ impl Context {
     }
 
     pub fn get_label(&self, name: &str) -> Value {
-        self.labels.get(name).unwrap().clone()
+        *self.labels.get(name).unwrap()
+    }
+
+    pub fn find_label(&self, name: &str) -> Option<Value> {
+        self.labels.get(name).copied()
+    }
+
+    /// Returns the address of a label used as argument `arg` of the current
+    /// instruction. Labels declared later resolve to `0` until patched.
+    pub fn label_address(&mut self, name: &str, arg: usize) -> Value {
+        if let Some(address) = self.find_label(name) {
+            return address;
+        }
+        self.forward_refs.push(ForwardRef {
+            label: name.to_string(),
+            instruction: self.instruction_number,
+            arg,
+            location: self.location.clone(),
+        });
+        0
+    }
+
+    pub fn take_forward_refs(&mut self) -> Vec<ForwardRef> {
+        std::mem::take(&mut self.forward_refs)
+    }
+
+    pub fn has_const(&self, name: &str) -> bool {
+        self.consts.contains_key(name)
+    }
+
+    pub fn add_const(&mut self, name: &str, value: Value) {
+        self.consts.insert(name.to_string(), value);
+    }
+
+    pub fn get_const(&self, name: &str) -> Option<Value> {
+        self.consts.get(name).copied()
+    }
+
+    pub fn has_macro(&self, name: &str) -> bool {
+        self.macros.contains_key(name)
+    }
+
+    pub fn add_macro(&mut self, m: Macro) {
+        self.macros.insert(m.name.clone(), m);
+    }
+
+    pub fn get_macro(&self, name: &str) -> Option<&Macro> {
+        self.macros.get(name)
+    }
+
+    /// Returns a number identifying the next macro expansion.
+    pub fn next_expansion(&mut self) -> usize {
+        self.expansions += 1;
+        self.expansions
+    }
+
+    /// Returns the visible variables ordered by address.
+    pub fn vars(&self) -> Vec<(String, Value)> {
+        let mut vars: Vec<(String, Value)> = self
+            .data
+            .iter()
+            .map(|(name, address)| (name.clone(), *address))
+            .collect();
+        vars.sort_by_key(|(_, address)| *address);
+        vars
     }
 
     pub fn get_var(&self, name: &str) -> Option<Value> {
-        self.data.get(name).map(|v| *v)
+        self.data.get(name).copied()
     }
 
     pub fn set_args(&mut self, args: Vec<String>) {
@@ -69,7 +225,57 @@ impl Context {
         self.args.len()
     }
 
+    /// Number of variable slots the program needs, i.e. the most variables
+    /// visible at once.
     pub fn data_size(&self) -> usize {
-        self.data.len()
+        self.data_size
+    }
+
+    pub fn add_string(&mut self, s: String) -> Result<Value, String> {
+        match self.strings.iter().position(|existing| *existing == s) {
+            Some(index) => Ok(index as Value),
+            None => {
+                let index = Self::next_index(self.strings.len(), "strings")?;
+                self.strings.push(s);
+                Ok(index)
+            }
+        }
+    }
+
+    /// Index of the next entry of a table holding `len` entries. Tables are
+    /// limited to `Value::MAX` entries so that their size fits in a `Value`.
+    fn next_index(len: usize, what: &str) -> Result<Value, String> {
+        match Value::try_from(len) {
+            Ok(index) if index < Value::MAX => Ok(index),
+            _ => Err(format!("program has more than {} {}", Value::MAX, what)),
+        }
+    }
+
+    pub fn add_param(&mut self, name: &str, address: Value) {
+        self.params.push((name.to_string(), address));
+    }
+
+    pub fn take_params(&mut self) -> Vec<(String, Value)> {
+        std::mem::take(&mut self.params)
+    }
+
+    pub fn take_strings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.strings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_string_limit() {
+        let mut ctx = Context::new();
+        ctx.strings = (0..Value::MAX).map(|i| i.to_string()).collect();
+        assert_eq!(ctx.add_string("7".to_string()), Ok(7));
+        assert_eq!(
+            ctx.add_string("x".to_string()),
+            Err("program has more than 65535 strings".to_string())
+        );
     }
 }
@@ -0,0 +1,81 @@
+use super::context::Context;
+use super::expr;
+
+/// Handles an assembler directive, i.e. a line starting with `.`. Directives
+/// configure the transpilation and never emit instructions themselves.
+pub fn parse(ctx: &mut Context, name: &str) -> Result<(), String> {
+    match name {
+        ".param" => parse_param(ctx),
+        ".const" => parse_const(ctx),
+        ".scope" => parse_scope(ctx),
+        ".endscope" => parse_endscope(ctx),
+        ".test" => parse_test(ctx),
+        ".include" => Err(".include is not allowed inside a macro".to_string()),
+        other => Err(format!("unknown directive: {}", other)),
+    }
+}
+
+fn parse_param(ctx: &mut Context) -> Result<(), String> {
+    if ctx.args_len() != 1 {
+        return Err(format!("expected 1 argument, got {}", ctx.args_len()));
+    }
+    let name = ctx.get_arg(0).unwrap().clone();
+    if !Context::is_var_name(&name) {
+        return Err(format!("invalid parameter name {}", name));
+    }
+    if ctx.has_var(&name) {
+        return Err(format!("parameter {} is already declared", name));
+    }
+    if ctx.in_scope() {
+        return Err(format!(
+            "parameter {} cannot be declared inside a scope",
+            name
+        ));
+    }
+    let address = ctx.add_var(&name)?;
+    ctx.add_param(&name, address);
+    Ok(())
+}
+
+fn parse_const(ctx: &mut Context) -> Result<(), String> {
+    if ctx.args_len() < 2 {
+        return Err(format!("expected 2 arguments, got {}", ctx.args_len()));
+    }
+    let name = ctx.get_arg(0).unwrap().clone();
+    if !Context::is_const_name(&name) {
+        return Err(format!("invalid constant name {}", name));
+    }
+    if ctx.has_const(&name) {
+        return Err(format!("constant {} is already defined", name));
+    }
+    let expression = (1..ctx.args_len())
+        .map(|i| ctx.get_arg(i).unwrap().as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let value = expr::eval(ctx, &expression)?;
+    ctx.add_const(&name, value);
+    Ok(())
+}
+
+fn parse_scope(ctx: &mut Context) -> Result<(), String> {
+    if ctx.args_len() != 0 {
+        return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+    }
+    ctx.open_scope();
+    Ok(())
+}
+
+fn parse_endscope(ctx: &mut Context) -> Result<(), String> {
+    if ctx.args_len() != 0 {
+        return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+    }
+    ctx.close_scope()
+}
+
+/// `.test` marks a file for `morango test` and has no effect on the program.
+fn parse_test(ctx: &mut Context) -> Result<(), String> {
+    if ctx.args_len() != 0 {
+        return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+    }
+    Ok(())
+}
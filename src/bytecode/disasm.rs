@@ -0,0 +1,156 @@
+use super::instruction::Instruction;
+use super::{lexer, ByteCode};
+use crate::config::OpCodes;
+
+/// Renders a human-readable listing of the bytecode: one instruction per line,
+/// prefixed with its address. Variable operands are shown as `[address]` and
+/// jump targets as instruction addresses, with the SWITCH default target last.
+/// ASSERT is followed by its source line as a comment.
+pub fn disassemble(bytecode: &ByteCode) -> String {
+    let mut out = format!("; data size: {}\n", bytecode.data_size);
+    for (name, address) in &bytecode.params {
+        out.push_str(&format!("; param {} = [0x{:02x}]\n", name, address));
+    }
+    for (ip, instruction) in bytecode.instructions.iter().enumerate() {
+        out.push_str(&format!("{:04x}: {}\n", ip, render(bytecode, instruction)));
+    }
+    out
+}
+
+fn render(bytecode: &ByteCode, instruction: &Instruction) -> String {
+    let opcode = match instruction.opcode {
+        Some(opcode) => opcode,
+        None => return "<empty opcode>".to_string(),
+    };
+    let args = instruction.args.as_deref().unwrap_or(&[]);
+    let mut operands = args
+        .iter()
+        .enumerate()
+        .map(|(index, arg)| match opcode {
+            OpCodes::WRT | OpCodes::READ => format!("[0x{:02x}]", arg),
+            OpCodes::GOTO | OpCodes::JUMP | OpCodes::GOTZ | OpCodes::SWCH | OpCodes::TRY => {
+                format!("{:04x}", arg)
+            }
+            OpCodes::PRNS | OpCodes::ASRT if index == 0 => {
+                match bytecode.strings.get(*arg as usize) {
+                    Some(s) => lexer::quote_string(s),
+                    None => format!("<invalid string 0x{:02x}>", arg),
+                }
+            }
+            OpCodes::HOST => match bytecode.strings.get(*arg as usize) {
+                Some(name) => name.clone(),
+                None => format!("<invalid string 0x{:02x}>", arg),
+            },
+            OpCodes::ASRT => match bytecode.strings.get(*arg as usize) {
+                Some(location) => format!("; {}", location),
+                None => format!("<invalid string 0x{:02x}>", arg),
+            },
+            _ => arg.to_string(),
+        })
+        .collect::<Vec<_>>();
+    if opcode == OpCodes::SWCH && !operands.is_empty() {
+        operands.insert(operands.len() - 1, "default".to_string());
+    }
+    if operands.is_empty() {
+        opcode.mnemonic().to_string()
+    } else {
+        format!("{} {}", opcode.mnemonic(), operands.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    #[test]
+    fn disassemble_program() {
+        let code = concat!(
+            ".param n\n",
+            "PRINT_STR \"n is\\t\"\n",
+            "&label\n",
+            "READ_VAR n\n",
+            "LOAD_VAL 7\n",
+            "GOTO &label\n",
+            "RETURN_VALUE\n",
+        );
+        let mut reader = BufReader::new(code.as_bytes());
+        let bytecode = ByteCode::do_transpile(&mut reader).unwrap();
+        assert_eq!(
+            disassemble(&bytecode),
+            concat!(
+                "; data size: 1\n",
+                "; param n = [0x00]\n",
+                "0000: PRINT_STR \"n is\\t\"\n",
+                "0001: READ_VAR [0x00]\n",
+                "0002: LOAD_VAL 7\n",
+                "0003: GOTO 0001\n",
+                "0004: RETURN_VALUE\n",
+            )
+        );
+    }
+
+    #[test]
+    fn disassemble_bitwise() {
+        let code = "LOAD_VAL 0x0F | NOT | LOAD_VAL 4 | SHL | RETURN_VALUE";
+        let mut reader = BufReader::new(code.as_bytes());
+        let bytecode = ByteCode::do_transpile(&mut reader).unwrap();
+        assert_eq!(
+            disassemble(&bytecode),
+            concat!(
+                "; data size: 0\n",
+                "0000: LOAD_VAL 15\n",
+                "0001: NOT\n",
+                "0002: LOAD_VAL 4\n",
+                "0003: SHL\n",
+                "0004: RETURN_VALUE\n",
+            )
+        );
+    }
+
+    #[test]
+    fn disassemble_assert() {
+        let code = ".test\nLOAD_VAL 1\nASSERT \"one is true\"";
+        let mut reader = BufReader::new(code.as_bytes());
+        let bytecode = ByteCode::do_transpile(&mut reader).unwrap();
+        assert_eq!(
+            disassemble(&bytecode),
+            concat!(
+                "; data size: 0\n",
+                "0000: LOAD_VAL 1\n",
+                "0001: ASSERT \"one is true\" ; line 3\n",
+            )
+        );
+    }
+
+    #[test]
+    fn disassemble_call_host() {
+        let code = "LOAD_VAL 1\nCALL_HOST price";
+        let mut reader = BufReader::new(code.as_bytes());
+        let bytecode = ByteCode::do_transpile(&mut reader).unwrap();
+        assert_eq!(
+            disassemble(&bytecode),
+            concat!(
+                "; data size: 0\n",
+                "0000: LOAD_VAL 1\n",
+                "0001: CALL_HOST price\n",
+            )
+        );
+    }
+
+    #[test]
+    fn disassemble_switch() {
+        let code = "&a | LOAD_VAL 1 | SWITCH &a &b default &c | &b | &c | RETURN_VALUE";
+        let mut reader = BufReader::new(code.as_bytes());
+        let bytecode = ByteCode::do_transpile(&mut reader).unwrap();
+        assert_eq!(
+            disassemble(&bytecode),
+            concat!(
+                "; data size: 0\n",
+                "0000: LOAD_VAL 1\n",
+                "0001: SWITCH 0000 0002 default 0002\n",
+                "0002: RETURN_VALUE\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! Constant expressions evaluated at transpile time, e.g. `W * H + 1`.
+//!
+//! Operands are numeric literals and constants declared with `.const`.
+//! Supported operators are `+`, `-`, `*`, `/` and `%` with the usual
+//! precedence, and parentheses. Intermediate results may be negative, the
+//! final value must fit in `Value`.
+
+use super::context::Context;
+use super::literal;
+use crate::config::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Op(char),
+    Open,
+    Close,
+}
+
+/// Returns `true` if `token` is meant as an expression rather than a single
+/// numeric literal, i.e. it names a constant or contains an operator.
+pub fn is_expression(token: &str) -> bool {
+    token.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '(')
+        || token.contains(['+', '*', '/', '%', '(', ')'])
+        || token.get(1..).is_some_and(|rest| rest.contains('-'))
+}
+
+pub fn eval(ctx: &Context, expr: &str) -> Result<Value, String> {
+    let tokens = tokenize(ctx, expr)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        expr,
+    };
+    let value = parser.sum()?;
+    if parser.pos != parser.tokens.len() {
+        return Err(format!("invalid constant expression {}", expr));
+    }
+    match Value::try_from(value) {
+        Ok(v) => Ok(v),
+        Err(_) => Err(format!(
+            "constant expression {} = {} does not fit in Value (0..={})",
+            expr,
+            value,
+            Value::MAX
+        )),
+    }
+}
+
+fn tokenize(ctx: &Context, expr: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = expr.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        match c {
+            '+' | '-' | '*' | '/' | '%' => tokens.push(Token::Op(c)),
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '\'' => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && chars[i] != '\'' {
+                    if chars[i] == '\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                let word: String = chars[start..(i + 1).min(chars.len())].iter().collect();
+                tokens.push(Token::Number(i64::from(literal::parse(&word)?)));
+            }
+            c if c.is_ascii_alphanumeric() || c == '_' => {
+                let start = i;
+                while i + 1 < chars.len()
+                    && (chars[i + 1].is_ascii_alphanumeric() || chars[i + 1] == '_')
+                {
+                    i += 1;
+                }
+                let word: String = chars[start..=i].iter().collect();
+                let value = if c.is_ascii_digit() {
+                    literal::parse(&word)?
+                } else {
+                    match ctx.get_const(&word) {
+                        Some(v) => v,
+                        None => return Err(format!("undefined constant {}", word)),
+                    }
+                };
+                tokens.push(Token::Number(i64::from(value)));
+            }
+            other => {
+                return Err(format!(
+                    "unexpected character `{}` in constant expression {}",
+                    other, expr
+                ))
+            }
+        }
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    expr: &'a str,
+}
+
+impl Parser<'_> {
+    fn invalid(&self) -> String {
+        format!("invalid constant expression {}", self.expr)
+    }
+
+    fn next_op(&self, ops: &[char]) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) if ops.contains(op) => Some(*op),
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> Result<i64, String> {
+        let mut value = self.product()?;
+        while let Some(op) = self.next_op(&['+', '-']) {
+            self.pos += 1;
+            let rhs = self.product()?;
+            let result = if op == '+' {
+                value.checked_add(rhs)
+            } else {
+                value.checked_sub(rhs)
+            };
+            value = result.ok_or_else(|| self.invalid())?;
+        }
+        Ok(value)
+    }
+
+    fn product(&mut self) -> Result<i64, String> {
+        let mut value = self.unary()?;
+        while let Some(op) = self.next_op(&['*', '/', '%']) {
+            self.pos += 1;
+            let rhs = self.unary()?;
+            if op != '*' && rhs == 0 {
+                return Err(format!(
+                    "division by zero in constant expression {}",
+                    self.expr
+                ));
+            }
+            value = match op {
+                '*' => value.checked_mul(rhs).ok_or_else(|| self.invalid())?,
+                '/' => value / rhs,
+                _ => value % rhs,
+            };
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        if self.next_op(&['-']).is_some() {
+            self.pos += 1;
+            return self.unary()?.checked_neg().ok_or_else(|| self.invalid());
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<i64, String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Number(v)) => Ok(v),
+            Some(Token::Open) => {
+                let value = self.sum()?;
+                if self.tokens.get(self.pos) != Some(&Token::Close) {
+                    return Err(format!(
+                        "unbalanced parentheses in constant expression {}",
+                        self.expr
+                    ));
+                }
+                self.pos += 1;
+                Ok(value)
+            }
+            _ => Err(self.invalid()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> Context {
+        let mut ctx = Context::new();
+        ctx.add_const("W", 4);
+        ctx.add_const("H", 3);
+        ctx
+    }
+
+    #[test]
+    fn eval_precedence() {
+        let ctx = context();
+        assert_eq!(eval(&ctx, "W*H"), Ok(12));
+        assert_eq!(eval(&ctx, "1 + W * H"), Ok(13));
+        assert_eq!(eval(&ctx, "(1 + W) * H"), Ok(15));
+        assert_eq!(eval(&ctx, "W - H - 1"), Ok(0));
+        assert_eq!(eval(&ctx, "0x10 / W % 3"), Ok(1));
+        assert_eq!(eval(&ctx, "-H + W"), Ok(1));
+        assert_eq!(eval(&ctx, "'a' + 1"), Ok(98));
+    }
+
+    #[test]
+    fn eval_errors() {
+        let ctx = context();
+        assert_eq!(eval(&ctx, "W * D"), Err("undefined constant D".to_string()));
+        assert_eq!(
+            eval(&ctx, "H - W"),
+            Err("constant expression H - W = -1 does not fit in Value (0..=65535)".to_string())
+        );
+        assert_eq!(
+            eval(&ctx, "W / (H - 3)"),
+            Err("division by zero in constant expression W / (H - 3)".to_string())
+        );
+        assert_eq!(
+            eval(&ctx, "(W"),
+            Err("unbalanced parentheses in constant expression (W".to_string())
+        );
+        assert_eq!(
+            eval(&ctx, "W H"),
+            Err("invalid constant expression W H".to_string())
+        );
+        assert_eq!(
+            eval(&ctx, "W & H"),
+            Err("unexpected character `&` in constant expression W & H".to_string())
+        );
+    }
+}
@@ -0,0 +1,58 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Resolves `.include` paths and keeps track of the files being transpiled to
+/// detect include cycles.
+#[derive(Debug, Default)]
+pub struct Includes {
+    dirs: Vec<PathBuf>,
+    /// Canonical path and display name of every file being transpiled, the
+    /// innermost last.
+    stack: Vec<(PathBuf, String)>,
+}
+
+impl Includes {
+    pub fn new(dirs: &[PathBuf]) -> Self {
+        Includes {
+            dirs: dirs.to_vec(),
+            stack: Vec::new(),
+        }
+    }
+
+    /// Finds an included file, first relative to the including file (or the
+    /// working directory when transpiling a reader), then in the include dirs.
+    pub fn resolve(&self, name: &str, from: Option<&Path>) -> Result<PathBuf, String> {
+        let base = from.and_then(Path::parent).unwrap_or_else(|| Path::new(""));
+        let candidates =
+            std::iter::once(base.join(name)).chain(self.dirs.iter().map(|dir| dir.join(name)));
+        for candidate in candidates {
+            if candidate.is_file() {
+                return Ok(candidate);
+            }
+        }
+        Err(format!("included file {} not found", name))
+    }
+
+    /// Marks `path` as being transpiled, failing if it is already on the stack.
+    pub fn enter(&mut self, path: &Path) -> Result<(), String> {
+        let canonical = match fs::canonicalize(path) {
+            Ok(canonical) => canonical,
+            Err(e) => return Err(format!("Unable to open file: {}", e)),
+        };
+        let name = path.display().to_string();
+        if let Some(start) = self.stack.iter().position(|(p, _)| *p == canonical) {
+            let mut cycle: Vec<&str> = self.stack[start..]
+                .iter()
+                .map(|(_, n)| n.as_str())
+                .collect();
+            cycle.push(&name);
+            return Err(format!("include cycle: {}", cycle.join(" -> ")));
+        }
+        self.stack.push((canonical, name));
+        Ok(())
+    }
+
+    pub fn leave(&mut self) {
+        self.stack.pop();
+    }
+}
@@ -1,4 +1,5 @@
 use super::context::Context;
+use super::{directive, lexer};
 use crate::config::{OpCodes, Value, Visitor};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -12,10 +13,10 @@ impl Instruction {
         if s.is_empty() {
             return Err("Empty instruction".to_string());
         }
-        let s_split = s
-            .split_whitespace()
-            .map(|s| s.to_string())
-            .collect::<Vec<_>>();
+        let s_split = lexer::tokenize(s)?;
+        if s_split.is_empty() {
+            return Err("Empty instruction".to_string());
+        }
         ctx.set_args(s_split[1..].to_vec());
         let mut instr = Instruction {
             opcode: None,
@@ -35,12 +36,50 @@ impl Instruction {
             "TEST_LT" => instr.visit_test_lt(ctx),
             "DUP" => instr.visit_dup(ctx),
             "POP" => instr.visit_pop(ctx),
+            "PRINT" => instr.visit_print(ctx),
+            "PRINT_STR" => instr.visit_print_str(ctx),
+            "READ_INPUT" => instr.visit_read_input(ctx),
+            "SWAP" => instr.visit_swap(ctx),
+            "OVER" => instr.visit_over(ctx),
+            "ROT" => instr.visit_rot(ctx),
+            "PICK" => instr.visit_pick(ctx),
+            "DEPTH" => instr.visit_depth(ctx),
+            "AND" => instr.visit_and(ctx),
+            "OR" => instr.visit_or(ctx),
+            "XOR" => instr.visit_xor(ctx),
+            "NOT" => instr.visit_not(ctx),
+            "SHL" => instr.visit_shl(ctx),
+            "SHR" => instr.visit_shr(ctx),
+            "TEST_NE" => instr.visit_test_ne(ctx),
+            "TEST_GE" => instr.visit_test_ge(ctx),
+            "TEST_LE" => instr.visit_test_le(ctx),
+            "BOOL_AND" => instr.visit_bool_and(ctx),
+            "BOOL_OR" => instr.visit_bool_or(ctx),
+            "BOOL_NOT" => instr.visit_bool_not(ctx),
+            "JUMP" => instr.visit_jump(ctx),
+            "GOTO_IF_ZERO" => instr.visit_goto_if_zero(ctx),
+            "SWITCH" => instr.visit_switch(ctx),
+            "TRY" => instr.visit_try(ctx),
+            "END_TRY" => instr.visit_end_try(ctx),
+            "THROW" => instr.visit_throw(ctx),
+            "ASSERT" => instr.visit_assert(ctx),
+            "CALL_HOST" => instr.visit_call_host(ctx),
+            "MIN" => instr.visit_min(ctx),
+            "MAX" => instr.visit_max(ctx),
+            "ABS" => instr.visit_abs(ctx),
+            "POW" => instr.visit_pow(ctx),
+            "GCD" => instr.visit_gcd(ctx),
+            "SQRT_INT" => instr.visit_sqrt_int(ctx),
+            "CLAMP" => instr.visit_clamp(ctx),
+            "RAND" => instr.visit_rand(ctx),
             other => {
                 if Context::is_label(other) {
                     if ctx.has_label(&s_split[0]) {
                         Err(format!("duplicated label: {}", s_split[0]))?;
                     }
                     ctx.add_label(&s_split[0], ctx.instruction_number);
+                } else if other.starts_with('.') {
+                    directive::parse(ctx, other)?;
                 } else {
                     Err(format!("unknown instruction: {}", &s_split[0]))?;
                 }
@@ -0,0 +1,280 @@
+/// Splits an instruction into whitespace-separated tokens. Double-quoted string
+/// literals and single-quoted character literals are kept as a single token,
+/// quotes included.
+pub fn tokenize(s: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                let quote = c;
+                current.push(c);
+                let mut terminated = false;
+                while let Some(c) = chars.next() {
+                    current.push(c);
+                    if c == '\\' {
+                        if let Some(escaped) = chars.next() {
+                            current.push(escaped);
+                        }
+                    } else if c == quote {
+                        terminated = true;
+                        break;
+                    }
+                }
+                if !terminated {
+                    let kind = if quote == '"' { "string" } else { "character" };
+                    return Err(format!("unterminated {} literal {}", kind, current));
+                }
+            }
+            c if c.is_whitespace() => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+/// Tracks whether a scan over a line is inside a string or character literal.
+#[derive(Default)]
+struct Quotes {
+    quote: Option<char>,
+    escaped: bool,
+}
+
+impl Quotes {
+    /// Feeds the next character and returns `true` if it is part of a literal.
+    fn inside(&mut self, c: char) -> bool {
+        match self.quote {
+            Some(_) if self.escaped => self.escaped = false,
+            Some(_) if c == '\\' => self.escaped = true,
+            Some(quote) if c == quote => self.quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => self.quote = Some(c),
+            None => return false,
+        }
+        true
+    }
+}
+
+/// Splits a line into code and a trailing comment. Comments start with `;`,
+/// or with `#` at the beginning of a token, and are never looked for inside
+/// string or character literals. The returned comment includes its marker.
+pub fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut quotes = Quotes::default();
+    let mut previous = ' ';
+    for (index, c) in line.char_indices() {
+        if !quotes.inside(c) && (c == ';' || (c == '#' && previous.is_whitespace())) {
+            return (&line[..index], Some(&line[index..]));
+        }
+        previous = c;
+    }
+    (line, None)
+}
+
+/// Splits a line into statements separated by `|`, ignoring separators inside
+/// string or character literals.
+pub fn split_statements(code: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut quotes = Quotes::default();
+    let mut start = 0;
+    for (index, c) in code.char_indices() {
+        if !quotes.inside(c) && c == '|' {
+            statements.push(&code[start..index]);
+            start = index + 1;
+        }
+    }
+    statements.push(&code[start..]);
+    statements
+}
+
+/// Unquotes a string literal token produced by `tokenize`, resolving the
+/// `\n`, `\t`, `\\`, `\"` and `\'` escape sequences.
+pub fn parse_string_literal(token: &str) -> Result<String, String> {
+    if token.len() < 2 || !token.starts_with('"') || !token.ends_with('"') {
+        return Err(format!("expected string literal, got {}", token));
+    }
+    unescape(&token[1..token.len() - 1], token)
+}
+
+/// Unquotes a character literal token such as `'a'` or `'\n'`.
+pub fn parse_char_literal(token: &str) -> Result<char, String> {
+    if token.len() < 2 || !token.starts_with('\'') || !token.ends_with('\'') {
+        return Err(format!("expected character literal, got {}", token));
+    }
+    let unescaped = unescape(&token[1..token.len() - 1], token)?;
+    let mut chars = unescaped.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(format!(
+            "character literal {} must contain exactly one character",
+            token
+        )),
+    }
+}
+
+fn unescape(body: &str, token: &str) -> Result<String, String> {
+    let mut result = String::new();
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('\\') => result.push('\\'),
+            Some('"') => result.push('"'),
+            Some('\'') => result.push('\''),
+            Some(other) => return Err(format!("invalid escape sequence \\{}", other)),
+            None => return Err(format!("unterminated literal {}", token)),
+        }
+    }
+    Ok(result)
+}
+
+/// Inverse of `parse_string_literal`.
+pub fn quote_string(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_plain() {
+        assert_eq!(
+            tokenize("  LOAD_VAL   1 "),
+            Ok(vec!["LOAD_VAL".to_string(), "1".to_string()])
+        );
+    }
+
+    #[test]
+    fn tokenize_string_literal() {
+        assert_eq!(
+            tokenize(r#"PRINT_STR "hello, \"big\" world""#),
+            Ok(vec![
+                "PRINT_STR".to_string(),
+                r#""hello, \"big\" world""#.to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn tokenize_unterminated_string_literal() {
+        assert_eq!(
+            tokenize(r#"PRINT_STR "hello"#),
+            Err(r#"unterminated string literal "hello"#.to_string())
+        );
+    }
+
+    #[test]
+    fn split_comment_variants() {
+        assert_eq!(split_comment("ADD"), ("ADD", None));
+        assert_eq!(split_comment("ADD ; sum"), ("ADD ", Some("; sum")));
+        assert_eq!(split_comment("# note"), ("", Some("# note")));
+        assert_eq!(split_comment("ADD;x"), ("ADD", Some(";x")));
+        assert_eq!(split_comment("LOAD_VAL 1#2"), ("LOAD_VAL 1#2", None));
+        assert_eq!(
+            split_comment(r#"PRINT_STR "a ; \" # b" ; c"#),
+            (r#"PRINT_STR "a ; \" # b" "#, Some("; c"))
+        );
+    }
+
+    #[test]
+    fn tokenize_char_literal() {
+        assert_eq!(
+            tokenize("LOAD_VAL ' '"),
+            Ok(vec!["LOAD_VAL".to_string(), "' '".to_string()])
+        );
+        assert_eq!(
+            tokenize("LOAD_VAL 'a"),
+            Err("unterminated character literal 'a".to_string())
+        );
+    }
+
+    #[test]
+    fn split_ignores_char_literals() {
+        assert_eq!(
+            split_comment("LOAD_VAL ';' ; c"),
+            ("LOAD_VAL ';' ", Some("; c"))
+        );
+        assert_eq!(
+            split_statements("LOAD_VAL '|' | POP"),
+            vec!["LOAD_VAL '|' ", " POP"]
+        );
+    }
+
+    #[test]
+    fn parse_char_literal_variants() {
+        assert_eq!(parse_char_literal("'a'"), Ok('a'));
+        assert_eq!(parse_char_literal("'\\n'"), Ok('\n'));
+        assert_eq!(parse_char_literal("'\\''"), Ok('\''));
+        assert_eq!(
+            parse_char_literal("'ab'"),
+            Err("character literal 'ab' must contain exactly one character".to_string())
+        );
+    }
+
+    #[test]
+    fn split_statements_variants() {
+        assert_eq!(split_statements("ADD"), vec!["ADD"]);
+        assert_eq!(
+            split_statements("LOAD_VAL 1 | LOAD_VAL 2|ADD"),
+            vec!["LOAD_VAL 1 ", " LOAD_VAL 2", "ADD"]
+        );
+        assert_eq!(
+            split_statements(r#"PRINT_STR "a | b" | POP"#),
+            vec![r#"PRINT_STR "a | b" "#, " POP"]
+        );
+    }
+
+    #[test]
+    fn parse_string_literal_escapes() {
+        assert_eq!(
+            parse_string_literal(r#""a\tb\n\\\"""#),
+            Ok("a\tb\n\\\"".to_string())
+        );
+    }
+
+    #[test]
+    fn quote_string_roundtrip() {
+        let s = "tab\there \"quoted\" \\ newline\n";
+        assert_eq!(parse_string_literal(&quote_string(s)), Ok(s.to_string()));
+    }
+
+    #[test]
+    fn parse_string_literal_invalid_escape() {
+        assert_eq!(
+            parse_string_literal(r#""\q""#),
+            Err("invalid escape sequence \\q".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_string_literal_unquoted() {
+        assert_eq!(
+            parse_string_literal("hello"),
+            Err("expected string literal, got hello".to_string())
+        );
+    }
+}
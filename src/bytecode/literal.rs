@@ -0,0 +1,142 @@
+//! Numeric literals accepted by `LOAD_VAL`: decimal (`42`), hexadecimal
+//! (`0x2A`), binary (`0b101010`), octal (`0o52`) and character (`'*'`)
+//! literals. Digits may be separated with underscores (`1_000`).
+
+use super::lexer;
+use crate::config::Value;
+
+pub fn parse(token: &str) -> Result<Value, String> {
+    if token.starts_with('\'') {
+        let c = lexer::parse_char_literal(token)?;
+        return match Value::try_from(u32::from(c)) {
+            Ok(v) => Ok(v),
+            Err(_) => Err(format!(
+                "literal {} does not fit in Value (max {})",
+                token,
+                Value::MAX
+            )),
+        };
+    }
+    if let Some(digits) = token.strip_prefix('-') {
+        if digits.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(format!(
+                "literal {} does not fit in Value (min {})",
+                token,
+                Value::MIN
+            ));
+        }
+    }
+    let (radix, kind, digits) = match token.get(..2) {
+        Some("0x") | Some("0X") => (16, "hexadecimal", &token[2..]),
+        Some("0b") | Some("0B") => (2, "binary", &token[2..]),
+        Some("0o") | Some("0O") => (8, "octal", &token[2..]),
+        _ => (10, "decimal", token),
+    };
+    if !digits.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        return Err(format!("invalid {} literal {}", kind, token));
+    }
+    let mut value: u32 = 0;
+    let mut overflow = false;
+    for c in digits.chars() {
+        if c == '_' {
+            continue;
+        }
+        let digit = match c.to_digit(radix) {
+            Some(digit) => digit,
+            None => {
+                return Err(format!(
+                    "invalid digit `{}` in {} literal {}",
+                    c, kind, token
+                ))
+            }
+        };
+        value = value * radix + digit;
+        if value > u32::from(Value::MAX) {
+            overflow = true;
+            value = u32::from(Value::MAX);
+        }
+    }
+    if overflow {
+        return Err(format!(
+            "literal {} does not fit in Value (max {})",
+            token,
+            Value::MAX
+        ));
+    }
+    Ok(value as Value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_radixes() {
+        assert_eq!(parse("42"), Ok(42));
+        assert_eq!(parse("0x1F"), Ok(0x1F));
+        assert_eq!(parse("0xff"), Ok(0xFF));
+        assert_eq!(parse("0b1010"), Ok(10));
+        assert_eq!(parse("0o17"), Ok(15));
+        assert_eq!(parse("65535"), Ok(65535));
+    }
+
+    #[test]
+    fn parse_underscores() {
+        assert_eq!(parse("1_000"), Ok(1000));
+        assert_eq!(parse("0b1111_0000"), Ok(0xF0));
+        assert_eq!(
+            parse("0x_ff"),
+            Err("invalid hexadecimal literal 0x_ff".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_char() {
+        assert_eq!(parse("'a'"), Ok(97));
+        assert_eq!(parse("'\\n'"), Ok(10));
+        assert_eq!(
+            parse("'😀'"),
+            Err("literal '😀' does not fit in Value (max 65535)".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_out_of_range() {
+        assert_eq!(
+            parse("70000"),
+            Err("literal 70000 does not fit in Value (max 65535)".to_string())
+        );
+        assert_eq!(
+            parse("0x1_0000"),
+            Err("literal 0x1_0000 does not fit in Value (max 65535)".to_string())
+        );
+        assert_eq!(
+            parse("99999999999999999999999"),
+            Err("literal 99999999999999999999999 does not fit in Value (max 65535)".to_string())
+        );
+        assert_eq!(
+            parse("-5"),
+            Err("literal -5 does not fit in Value (min 0)".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_eq!(
+            parse("0x"),
+            Err("invalid hexadecimal literal 0x".to_string())
+        );
+        assert_eq!(
+            parse("0b102"),
+            Err("invalid digit `2` in binary literal 0b102".to_string())
+        );
+        assert_eq!(
+            parse("12ab"),
+            Err("invalid digit `a` in decimal literal 12ab".to_string())
+        );
+        assert_eq!(
+            parse("x"),
+            Err("invalid digit `x` in decimal literal x".to_string())
+        );
+    }
+}
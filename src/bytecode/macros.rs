@@ -0,0 +1,166 @@
+//! Assembler macros.
+//!
+//! ```text
+//! .macro count_to counter limit label
+//! READ_VAR %counter
+//! ...
+//! GOTO %label
+//! .endmacro
+//! ```
+//!
+//! A macro is invoked like an instruction, `count_to i 10 &loop`, and its body
+//! is transpiled in place with every `%param` replaced by the matching
+//! argument. Labels declared inside the body are local to each expansion.
+
+use super::context::Context;
+use super::lexer;
+use crate::config::OpCodes;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Macro {
+    pub name: String,
+    /// Line of the `.macro` directive.
+    pub line: usize,
+    params: Vec<String>,
+    body: Vec<(usize, Vec<String>)>,
+    labels: Vec<String>,
+}
+
+impl Macro {
+    /// Parses the arguments of a `.macro` directive: the macro name followed
+    /// by its parameter names.
+    pub fn parse(args: &[String], line: usize) -> Result<Self, String> {
+        let (name, params) = match args.split_first() {
+            Some(split) => split,
+            None => return Err("expected macro name".to_string()),
+        };
+        if !Context::is_const_name(name) {
+            return Err(format!("invalid macro name {}", name));
+        }
+        // `fmt` upper-cases mnemonics regardless of case, so `add` cannot be
+        // told apart from `ADD` either.
+        if OpCodes::from_mnemonic(&name.to_uppercase()).is_some() {
+            return Err(format!(
+                "macro name {} is reserved for an instruction",
+                name
+            ));
+        }
+        for (index, param) in params.iter().enumerate() {
+            if !Context::is_const_name(param) {
+                return Err(format!("invalid macro parameter name {}", param));
+            }
+            if params[..index].contains(param) {
+                return Err(format!("macro parameter {} is declared twice", param));
+            }
+        }
+        Ok(Macro {
+            name: name.clone(),
+            line,
+            params: params.to_vec(),
+            body: Vec::new(),
+            labels: Vec::new(),
+        })
+    }
+
+    /// Appends a statement of the macro body.
+    pub fn push(&mut self, statement: &str, line: usize) -> Result<(), String> {
+        let tokens = lexer::tokenize(statement)?;
+        for token in &tokens {
+            if let Some(param) = token.strip_prefix('%') {
+                if !self.params.iter().any(|p| p == param) {
+                    return Err(format!(
+                        "unknown parameter {} in macro {}",
+                        token, self.name
+                    ));
+                }
+            }
+        }
+        if let Some(head) = tokens.first() {
+            if Context::is_label(head) {
+                self.labels.push(head.clone());
+            }
+        }
+        self.body.push((line, tokens));
+        Ok(())
+    }
+
+    /// Returns the body statements with parameters substituted and local labels
+    /// renamed after the expansion number `id`, each with its source line.
+    pub fn expand(&self, args: &[String], id: usize) -> Result<Vec<(usize, String)>, String> {
+        if args.len() != self.params.len() {
+            return Err(format!(
+                "macro {} expects {} argument(s), got {}",
+                self.name,
+                self.params.len(),
+                args.len()
+            ));
+        }
+        let expanded = self.body.iter().map(|(line, tokens)| {
+            let tokens: Vec<String> = tokens
+                .iter()
+                .map(|token| match token.strip_prefix('%') {
+                    Some(param) => {
+                        let index = self.params.iter().position(|p| p == param).unwrap();
+                        args[index].clone()
+                    }
+                    None if self.labels.contains(token) => format!("{}@{}", token, id),
+                    None => token.clone(),
+                })
+                .collect();
+            (*line, tokens.join(" "))
+        });
+        Ok(expanded.collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn expand_substitutes_params_and_labels() {
+        let mut m = Macro::parse(&args(&["twice", "x"]), 1).unwrap();
+        m.push("&again", 2).unwrap();
+        m.push("READ_VAR %x", 3).unwrap();
+        m.push("GOTO &again", 4).unwrap();
+        m.push("GOTO &outer", 5).unwrap();
+        assert_eq!(
+            m.expand(&args(&["n"]), 7),
+            Ok(vec![
+                (2, "&again@7".to_string()),
+                (3, "READ_VAR n".to_string()),
+                (4, "GOTO &again@7".to_string()),
+                (5, "GOTO &outer".to_string()),
+            ])
+        );
+        assert_eq!(
+            m.expand(&[], 8),
+            Err("macro twice expects 1 argument(s), got 0".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_eq!(
+            Macro::parse(&args(&["ADD"]), 1),
+            Err("macro name ADD is reserved for an instruction".to_string())
+        );
+        assert_eq!(
+            Macro::parse(&args(&["add"]), 1),
+            Err("macro name add is reserved for an instruction".to_string())
+        );
+        assert_eq!(
+            Macro::parse(&args(&["m", "a", "a"]), 1),
+            Err("macro parameter a is declared twice".to_string())
+        );
+        let mut m = Macro::parse(&args(&["m", "a"]), 1).unwrap();
+        assert_eq!(
+            m.push("LOAD_VAL %b", 2),
+            Err("unknown parameter %b in macro m".to_string())
+        );
+    }
+}
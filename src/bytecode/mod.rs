@@ -1,22 +1,48 @@
+mod binary;
 mod context;
+mod directive;
+mod disasm;
+mod expr;
+mod include;
 pub mod instruction;
+pub mod lexer;
+mod literal;
+mod macros;
+mod verifier;
 
 use crate::config::{OpCodes, Value, Visitor};
 use context::Context;
+use include::Includes;
 use instruction::Instruction;
 use lazy_static::lazy_static;
+use macros::Macro;
 use regex::Regex;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+const MAX_MACRO_DEPTH: usize = 64;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ByteCode {
     instructions: Vec<Instruction>,
     data_size: usize,
+    strings: Vec<String>,
+    params: Vec<(String, Value)>,
+    /// Name and address of every variable visible at the end of the program.
+    vars: Vec<(String, Value)>,
 }
 
 impl ByteCode {
     pub fn transpile(source_file: &str) -> Result<Self, String> {
+        ByteCode::transpile_with_includes(source_file, &[])
+    }
+    /// Transpiles a source file, looking up `.include`d files that are not
+    /// found next to the including file in `include_dirs`.
+    pub fn transpile_with_includes(
+        source_file: &str,
+        include_dirs: &[PathBuf],
+    ) -> Result<Self, String> {
         let file = match File::open(source_file) {
             Ok(file) => file,
             Err(e) => return Err(format!("Unable to open file: {}", e)),
@@ -25,7 +51,55 @@ impl ByteCode {
             return Err("Empty file".to_string());
         }
         let mut reader = BufReader::new(file);
-        ByteCode::do_transpile(&mut reader)
+        let mut includes = Includes::new(include_dirs);
+        includes.enter(Path::new(source_file))?;
+        ByteCode::transpile_source(&mut reader, Some(Path::new(source_file)), &mut includes)
+    }
+    pub fn transpile_reader<R: BufRead>(reader: &mut R) -> Result<Self, String> {
+        ByteCode::do_transpile(reader)
+    }
+    /// Like `transpile_with_includes`, with `.include` paths relative to the
+    /// working directory.
+    pub fn transpile_reader_with_includes<R: BufRead>(
+        reader: &mut R,
+        include_dirs: &[PathBuf],
+    ) -> Result<Self, String> {
+        ByteCode::transpile_source(reader, None, &mut Includes::new(include_dirs))
+    }
+    /// Loads either a source file or bytecode written by `to_bytes`.
+    pub fn load(path: &str) -> Result<Self, String> {
+        ByteCode::load_with_includes(path, &[])
+    }
+    pub fn load_with_includes(path: &str, include_dirs: &[PathBuf]) -> Result<Self, String> {
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) => return Err(format!("Unable to open file: {}", e)),
+        };
+        if binary::is_binary(&bytes) {
+            ByteCode::from_bytes(&bytes)
+        } else {
+            ByteCode::transpile_with_includes(path, include_dirs)
+        }
+    }
+    pub fn is_binary(bytes: &[u8]) -> bool {
+        binary::is_binary(bytes)
+    }
+    /// Encodes the program in the binary format, failing if a count or
+    /// length does not fit in it.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        binary::encode(self)
+    }
+    /// Decodes bytecode written by `to_bytes` and verifies it.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let bytecode = binary::decode(bytes)?;
+        bytecode.verify()?;
+        Ok(bytecode)
+    }
+    pub fn verify(&self) -> Result<(), String> {
+        verifier::verify(self)
+    }
+    pub fn disassemble(&self) -> String {
+        disasm::disassemble(self)
     }
     pub fn get_instruction(&self, index: usize) -> Option<&Instruction> {
         self.instructions.get(index)
@@ -33,16 +107,37 @@ impl ByteCode {
     pub fn get_data_size(&self) -> usize {
         self.data_size
     }
+    pub fn get_strings(&self) -> &[String] {
+        &self.strings
+    }
+    pub fn get_params(&self) -> &[(String, Value)] {
+        &self.params
+    }
+    pub fn get_vars(&self) -> &[(String, Value)] {
+        &self.vars
+    }
     fn new() -> Self {
         ByteCode {
             instructions: Vec::new(),
             data_size: 0,
+            strings: Vec::new(),
+            params: Vec::new(),
+            vars: Vec::new(),
         }
     }
     fn add_instruction(&mut self, ctx: &mut Context, s_instr: &str) -> Result<(), String> {
         match Instruction::parse(ctx, s_instr) {
             Ok(instr) => {
                 if instr.opcode.is_some() {
+                    ctx.instruction_number = match ctx.instruction_number.checked_add(1) {
+                        Some(number) => number,
+                        None => {
+                            return Err(format!(
+                                "program has more than {} instructions",
+                                Value::MAX
+                            ))
+                        }
+                    };
                     self.instructions.push(instr);
                 }
                 Ok(())
@@ -50,28 +145,206 @@ impl ByteCode {
             Err(e) => Err(e),
         }
     }
+    fn define_macro(ctx: &Context, statement: &str, line: usize) -> Result<Macro, String> {
+        let tokens = lexer::tokenize(statement)?;
+        let m = Macro::parse(&tokens[1..], line)?;
+        if ctx.has_macro(&m.name) {
+            return Err(format!("macro {} is already defined", m.name));
+        }
+        Ok(m)
+    }
+    /// Transpiles a statement, expanding it first if it invokes a macro.
+    fn add_statement(
+        &mut self,
+        ctx: &mut Context,
+        statement: &str,
+        depth: usize,
+    ) -> Result<(), String> {
+        let tokens = lexer::tokenize(statement)?;
+        let m = match tokens.first().and_then(|name| ctx.get_macro(name)) {
+            Some(m) => m.clone(),
+            None => return self.add_instruction(ctx, statement),
+        };
+        if depth >= MAX_MACRO_DEPTH {
+            return Err(format!("macro {} is nested too deeply", m.name));
+        }
+        let id = ctx.next_expansion();
+        for (line, statement) in m.expand(&tokens[1..], id)? {
+            if let Err(e) = self.add_statement(ctx, &statement, depth + 1) {
+                return Err(format!("in macro {} at line {}: {}", m.name, line, e));
+            }
+        }
+        Ok(())
+    }
     fn do_transpile<R: BufRead>(reader: &mut R) -> Result<Self, String> {
+        ByteCode::transpile_source(reader, None, &mut Includes::default())
+    }
+    fn transpile_source<R: BufRead>(
+        reader: &mut R,
+        file: Option<&Path>,
+        includes: &mut Includes,
+    ) -> Result<Self, String> {
         let mut program = ByteCode::new();
         let mut ctx = Context::new();
+        program.add_source(&mut ctx, reader, file, true, includes)?;
+        if let Some(location) = ctx.open_scope_location() {
+            return Err(format!(
+                "Transpilation error at {}: .scope is missing .endscope",
+                location
+            ));
+        }
+        if program.instructions.is_empty() {
+            return Err("Empty program".to_string());
+        }
+        for r in ctx.take_forward_refs() {
+            let address = match ctx.find_label(&r.label) {
+                Some(address) => address,
+                None => {
+                    return Err(format!(
+                        "Transpilation error at {}: undeclared label `{}`",
+                        r.location, r.label
+                    ))
+                }
+            };
+            let instruction = &mut program.instructions[r.instruction as usize];
+            instruction.args.as_mut().unwrap()[r.arg] = address;
+        }
+        program.data_size = ctx.data_size();
+        program.strings = ctx.take_strings();
+        program.params = ctx.take_params();
+        program.vars = ctx.vars();
+        Ok(program)
+    }
+    /// Resolves the file named by an `.include` statement and marks it as
+    /// being transpiled.
+    fn enter_include(
+        statement: &str,
+        file: Option<&Path>,
+        includes: &mut Includes,
+    ) -> Result<PathBuf, String> {
+        let tokens = lexer::tokenize(statement)?;
+        if tokens.len() != 2 {
+            return Err(format!("expected 1 argument, got {}", tokens.len() - 1));
+        }
+        let name = lexer::parse_string_literal(&tokens[1])?;
+        let path = includes.resolve(&name, file)?;
+        includes.enter(&path)?;
+        Ok(path)
+    }
+    /// Transpiles the lines of a source file into `self`. Errors in the main
+    /// file are reported by line number, errors in included files by file
+    /// name and line number.
+    fn add_source<R: BufRead>(
+        &mut self,
+        ctx: &mut Context,
+        reader: &mut R,
+        file: Option<&Path>,
+        main: bool,
+        includes: &mut Includes,
+    ) -> Result<(), String> {
+        let location = |line: usize| match file {
+            Some(file) if !main => format!("{}:{}", file.display(), line),
+            _ => format!("line {}", line),
+        };
+        let mut definition: Option<Macro> = None;
         for (index, line) in reader.lines().enumerate() {
             let ln = match line {
                 Ok(line) => line,
-                Err(e) => return Err(format!("Error reading line {}: {}", index + 1, e)),
+                Err(e) => return Err(format!("Error reading {}: {}", location(index + 1), e)),
             };
-            if ln.is_empty() {
-                continue;
-            }
             ctx.line_number = index + 1;
-            if let Err(e) = program.add_instruction(&mut ctx, &ln) {
-                return Err(format!("Transpilation error at line {}: {}", index + 1, e));
+            ctx.location = location(index + 1);
+            let (code, _comment) = lexer::split_comment(&ln);
+            for statement in lexer::split_statements(code) {
+                if statement.trim().is_empty() {
+                    continue;
+                }
+                let head = statement.split_whitespace().next();
+                if definition.is_none() && head == Some(".include") {
+                    let included =
+                        ByteCode::enter_include(statement, file, includes).and_then(|path| {
+                            match File::open(&path) {
+                                Ok(f) => Ok((path, f)),
+                                Err(e) => Err(format!("Unable to open file: {}", e)),
+                            }
+                        });
+                    let (path, f) = match included {
+                        Ok(included) => included,
+                        Err(e) => {
+                            return Err(format!(
+                                "Transpilation error at {}: {}",
+                                location(index + 1),
+                                e
+                            ))
+                        }
+                    };
+                    self.add_source(ctx, &mut BufReader::new(f), Some(&path), false, includes)?;
+                    includes.leave();
+                    continue;
+                }
+                let result = match (definition.as_mut(), head) {
+                    (Some(_), Some(".endmacro")) => {
+                        match statement.split_whitespace().count() - 1 {
+                            0 => {
+                                ctx.add_macro(definition.take().unwrap());
+                                Ok(())
+                            }
+                            n => Err(format!("expected 0 arguments, got {}", n)),
+                        }
+                    }
+                    (Some(_), Some(".macro")) => {
+                        Err("nested macro definitions are not supported".to_string())
+                    }
+                    (Some(m), _) => m.push(statement, index + 1),
+                    (None, Some(".macro")) => ByteCode::define_macro(ctx, statement, index + 1)
+                        .map(|m| {
+                            definition = Some(m);
+                        }),
+                    (None, Some(".endmacro")) => Err(".endmacro without .macro".to_string()),
+                    (None, _) => self.add_statement(ctx, statement, 0),
+                };
+                if let Err(e) = result {
+                    return Err(format!(
+                        "Transpilation error at {}: {}",
+                        location(index + 1),
+                        e
+                    ));
+                }
             }
         }
-        if program.instructions.is_empty() {
-            return Err("Empty program".to_string());
+        if let Some(m) = definition {
+            return Err(format!(
+                "Transpilation error at {}: macro {} is missing .endmacro",
+                location(m.line),
+                m.name
+            ));
         }
-        program.data_size = ctx.data_size();
-        return Ok(program);
+        Ok(())
+    }
+}
+
+/// Parses a numeric operand: a literal, a constant or a constant expression.
+fn parse_value(ctx: &Context, arg: &str) -> Result<Value, String> {
+    match literal::parse(arg) {
+        Ok(v) => Ok(v),
+        Err(_) if expr::is_expression(arg) => expr::eval(ctx, arg),
+        Err(e) => Err(e),
+    }
+}
+
+/// Parses a jump target, the label given as argument `arg`.
+fn parse_label(ctx: &mut Context, arg: usize) -> Result<Value, String> {
+    parse_label_into(ctx, arg, arg)
+}
+
+/// Parses the label given as source argument `arg`, to be stored as argument
+/// `slot` of the instruction.
+fn parse_label_into(ctx: &mut Context, arg: usize, slot: usize) -> Result<Value, String> {
+    let name = ctx.get_arg(arg).unwrap().clone();
+    if !Context::is_label(&name) {
+        return Err(format!("invalid label name `{}`", name));
     }
+    Ok(ctx.label_address(&name, slot))
 }
 
 impl Visitor<Context> for Instruction {
@@ -79,13 +352,9 @@ impl Visitor<Context> for Instruction {
         if ctx.args_len() != 1 {
             return Err(format!("expected 1 argument, got {}", ctx.args_len()));
         }
-        let arg1 = match ctx.get_arg(0).unwrap().parse::<Value>() {
-            Ok(v) => v,
-            Err(e) => return Err(format!("Error on line {}: {}", ctx.line_number, e)),
-        };
+        let arg1 = parse_value(ctx, ctx.get_arg(0).unwrap())?;
         self.opcode = Some(OpCodes::LOAD);
         self.args = Some(vec![arg1]);
-        ctx.instruction_number += 1;
         Ok(())
     }
     fn visit_wrt(&mut self, ctx: &mut Context) -> Result<(), String> {
@@ -102,11 +371,10 @@ impl Visitor<Context> for Instruction {
         let var0 = ctx.get_var(&arg0);
         let address = match var0 {
             Some(v) => v,
-            None => ctx.add_var(&arg0),
+            None => ctx.add_var(&arg0)?,
         };
         self.opcode = Some(OpCodes::WRT);
         self.args = Some(vec![address]);
-        ctx.instruction_number += 1;
         Ok(())
     }
     fn visit_read(&mut self, ctx: &mut Context) -> Result<(), String> {
@@ -125,7 +393,6 @@ impl Visitor<Context> for Instruction {
         }
         self.opcode = Some(OpCodes::READ);
         self.args = Some(vec![ctx.get_var(arg0).unwrap()]);
-        ctx.instruction_number += 1;
         Ok(())
     }
     fn visit_add(&mut self, ctx: &mut Context) -> Result<(), String> {
@@ -134,7 +401,6 @@ impl Visitor<Context> for Instruction {
         }
         self.opcode = Some(OpCodes::ADD);
         self.args = None;
-        ctx.instruction_number += 1;
         Ok(())
     }
     fn visit_mult(&mut self, ctx: &mut Context) -> Result<(), String> {
@@ -143,7 +409,6 @@ impl Visitor<Context> for Instruction {
         }
         self.opcode = Some(OpCodes::MULT);
         self.args = None;
-        ctx.instruction_number += 1;
         Ok(())
     }
     fn visit_rtn(&mut self, ctx: &mut Context) -> Result<(), String> {
@@ -152,7 +417,6 @@ impl Visitor<Context> for Instruction {
         }
         self.opcode = Some(OpCodes::RTN);
         self.args = None;
-        ctx.instruction_number += 1;
         Ok(())
     }
     fn visit_test_eq(&mut self, ctx: &mut Context) -> Result<(), String> {
@@ -161,7 +425,6 @@ impl Visitor<Context> for Instruction {
         }
         self.opcode = Some(OpCodes::TEEQ);
         self.args = None;
-        ctx.instruction_number += 1;
         Ok(())
     }
     fn visit_test_gt(&mut self, ctx: &mut Context) -> Result<(), String> {
@@ -170,7 +433,6 @@ impl Visitor<Context> for Instruction {
         }
         self.opcode = Some(OpCodes::TEGT);
         self.args = None;
-        ctx.instruction_number += 1;
         Ok(())
     }
     fn visit_test_lt(&mut self, ctx: &mut Context) -> Result<(), String> {
@@ -179,23 +441,15 @@ impl Visitor<Context> for Instruction {
         }
         self.opcode = Some(OpCodes::TELT);
         self.args = None;
-        ctx.instruction_number += 1;
         Ok(())
     }
     fn visit_goto(&mut self, ctx: &mut Context) -> Result<(), String> {
         if ctx.args_len() != 1 {
             return Err(format!("expected 1 argument, got {}", ctx.args_len()));
         }
-        let arg0 = ctx.get_arg(0).unwrap();
-        if !Context::is_label(arg0) {
-            return Err(format!("invalid label name `{}`", arg0));
-        }
-        if !ctx.has_label(arg0) {
-            return Err(format!("undeclared label `{}`", arg0));
-        }
+        let target = parse_label(ctx, 0)?;
         self.opcode = Some(OpCodes::GOTO);
-        self.args = Some(vec![ctx.get_label(arg0)]);
-        ctx.instruction_number += 1;
+        self.args = Some(vec![target]);
         Ok(())
     }
     fn visit_dup(&mut self, ctx: &mut Context) -> Result<(), String> {
@@ -204,7 +458,6 @@ impl Visitor<Context> for Instruction {
         }
         self.opcode = Some(OpCodes::DUP);
         self.args = None;
-        ctx.instruction_number += 1;
         Ok(())
     }
     fn visit_pop(&mut self, ctx: &mut Context) -> Result<(), String> {
@@ -213,59 +466,859 @@ impl Visitor<Context> for Instruction {
         }
         self.opcode = Some(OpCodes::POP);
         self.args = None;
-        ctx.instruction_number += 1;
         Ok(())
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    impl ToString for Instruction {
-        fn to_string(&self) -> String {
-            let args = match &self.args {
-                Some(data) => data
-                    .iter()
-                    .map(|arg| format!("0x{:02x}", arg))
-                    .collect::<Vec<String>>()
-                    .join(" "),
-                None => "".to_string(),
-            };
-            format!(
-                "0x{:02X} {}",
-                self.opcode.expect("Illegal instruction: empty opcode") as u8,
-                args
-            )
-            .trim()
-            .to_string()
+    fn visit_print(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::PRNT);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_print_str(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 1 {
+            return Err(format!("expected 1 argument, got {}", ctx.args_len()));
         }
+        let literal = lexer::parse_string_literal(ctx.get_arg(0).unwrap())?;
+        let index = ctx.add_string(literal)?;
+        self.opcode = Some(OpCodes::PRNS);
+        self.args = Some(vec![index]);
+        Ok(())
     }
-
-    #[test]
-    fn transpilation_error() {
-        let code = concat!("READ_VAR x\n", "RETURN_VALUE");
+    fn visit_read_input(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::INPT);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_swap(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::SWAP);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_over(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::OVER);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_rot(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::ROT);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_pick(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 1 {
+            return Err(format!("expected 1 argument, got {}", ctx.args_len()));
+        }
+        let arg0 = parse_value(ctx, ctx.get_arg(0).unwrap())?;
+        self.opcode = Some(OpCodes::PICK);
+        self.args = Some(vec![arg0]);
+        Ok(())
+    }
+    fn visit_depth(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::DPTH);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_and(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::AND);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_or(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::OR);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_xor(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::XOR);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_not(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::NOT);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_shl(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::SHL);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_shr(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::SHR);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_test_ne(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::TENE);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_test_ge(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::TEGE);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_test_le(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::TELE);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_bool_and(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::BAND);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_bool_or(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::BOR);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_bool_not(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::BNOT);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_jump(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 1 {
+            return Err(format!("expected 1 argument, got {}", ctx.args_len()));
+        }
+        let target = parse_label(ctx, 0)?;
+        self.opcode = Some(OpCodes::JUMP);
+        self.args = Some(vec![target]);
+        Ok(())
+    }
+    fn visit_goto_if_zero(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 1 {
+            return Err(format!("expected 1 argument, got {}", ctx.args_len()));
+        }
+        let target = parse_label(ctx, 0)?;
+        self.opcode = Some(OpCodes::GOTZ);
+        self.args = Some(vec![target]);
+        Ok(())
+    }
+    fn visit_switch(&mut self, ctx: &mut Context) -> Result<(), String> {
+        let len = ctx.args_len();
+        if len < 2 || ctx.get_arg(len - 2).unwrap() != "default" {
+            return Err("expected `default &<label>` after the SWITCH targets".to_string());
+        }
+        let mut targets = Vec::with_capacity(len - 1);
+        for arg in 0..len - 2 {
+            targets.push(parse_label(ctx, arg)?);
+        }
+        targets.push(parse_label_into(ctx, len - 1, len - 2)?);
+        self.opcode = Some(OpCodes::SWCH);
+        self.args = Some(targets);
+        Ok(())
+    }
+    fn visit_try(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 1 {
+            return Err(format!("expected 1 argument, got {}", ctx.args_len()));
+        }
+        let target = parse_label(ctx, 0)?;
+        self.opcode = Some(OpCodes::TRY);
+        self.args = Some(vec![target]);
+        Ok(())
+    }
+    fn visit_end_try(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::ETRY);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_throw(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::THRW);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_assert(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 1 {
+            return Err(format!("expected 1 argument, got {}", ctx.args_len()));
+        }
+        let literal = lexer::parse_string_literal(ctx.get_arg(0).unwrap())?;
+        let index = ctx.add_string(literal)?;
+        let location = ctx.add_string(ctx.location.clone())?;
+        self.opcode = Some(OpCodes::ASRT);
+        self.args = Some(vec![index, location]);
+        Ok(())
+    }
+    fn visit_call_host(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 1 {
+            return Err(format!("expected 1 argument, got {}", ctx.args_len()));
+        }
+        let name = ctx.get_arg(0).unwrap().clone();
+        if !Context::is_const_name(&name) {
+            return Err(format!("invalid host function name {}", name));
+        }
+        let index = ctx.add_string(name)?;
+        self.opcode = Some(OpCodes::HOST);
+        self.args = Some(vec![index]);
+        Ok(())
+    }
+    fn visit_min(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::MIN);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_max(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::MAX);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_abs(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::ABS);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_pow(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::POW);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_gcd(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::GCD);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_sqrt_int(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::SQRT);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_clamp(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::CLMP);
+        self.args = None;
+        Ok(())
+    }
+    fn visit_rand(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if ctx.args_len() != 0 {
+            return Err(format!("expected 0 arguments, got {}", ctx.args_len()));
+        }
+        self.opcode = Some(OpCodes::RAND);
+        self.args = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl std::fmt::Display for Instruction {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let args = match &self.args {
+                Some(data) => data
+                    .iter()
+                    .map(|arg| format!("0x{:02x}", arg))
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                None => "".to_string(),
+            };
+            let s = format!(
+                "0x{:02X} {}",
+                self.opcode.expect("Illegal instruction: empty opcode") as u8,
+                args
+            );
+            write!(f, "{}", s.trim())
+        }
+    }
+
+    #[test]
+    fn transpilation_error() {
+        let code = concat!("READ_VAR x\n", "RETURN_VALUE");
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: undeclared variable x".to_string())
+        );
+    }
+
+    #[test]
+    fn add_load_instruction() {
+        let code = "LOAD_VAL 1";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions.len(), 1);
+        assert_eq!(bytecode.instructions[0].to_string(), "0x01 0x01");
+    }
+    #[test]
+    fn add_load_invalid_arg_num_instruction() {
+        let code = "LOAD_VAL 1 1";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: expected 1 argument, got 2".to_string())
+        );
+    }
+
+    #[test]
+    fn add_load_literal_instruction() {
+        let code = "LOAD_VAL 0x1F | LOAD_VAL 0b1010 | LOAD_VAL 1_000 | LOAD_VAL ' '";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions[0].to_string(), "0x01 0x1f");
+        assert_eq!(bytecode.instructions[1].to_string(), "0x01 0x0a");
+        assert_eq!(bytecode.instructions[2].to_string(), "0x01 0x3e8");
+        assert_eq!(bytecode.instructions[3].to_string(), "0x01 0x20");
+    }
+
+    #[test]
+    fn add_load_out_of_range_instruction() {
+        let code = "LOAD_VAL 1\nLOAD_VAL 70000";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err(
+                "Transpilation error at line 2: literal 70000 does not fit in Value (max 65535)"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn add_load_const_instruction() {
+        let code = ".const W 4\n.const H W - 1\n.const AREA W*H\nLOAD_VAL AREA\nLOAD_VAL AREA+W";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions.len(), 2);
+        assert_eq!(bytecode.instructions[0].to_string(), "0x01 0x0c");
+        assert_eq!(bytecode.instructions[1].to_string(), "0x01 0x10");
+        assert_eq!(bytecode.data_size, 0);
+    }
+
+    #[test]
+    fn add_const_redefinition() {
+        let code = ".const LIMIT 10\n.const LIMIT 20";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 2: constant LIMIT is already defined".to_string())
+        );
+    }
+
+    #[test]
+    fn add_load_undefined_const() {
+        let code = ".const LIMIT 10\nLOAD_VAL LIMT";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 2: undefined constant LIMT".to_string())
+        );
+    }
+
+    #[test]
+    fn add_invalid_const() {
+        for (code, error) in [
+            (".const LIMIT", "expected 2 arguments, got 1"),
+            (".const 1X 10", "invalid constant name 1X"),
+            (".const X*2 10", "invalid constant name X*2"),
+        ] {
+            let mut reader = BufReader::new(code.as_bytes());
+            let result = ByteCode::do_transpile(&mut reader);
+            assert_eq!(
+                result,
+                Err(format!("Transpilation error at line 1: {}", error))
+            );
+        }
+    }
+
+    #[test]
+    fn add_wrt_instruction() {
+        let code = "WRITE_VAR x";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions.len(), 1);
+        assert_eq!(bytecode.instructions[0].to_string(), "0x02 0x00");
+    }
+
+    #[test]
+    fn add_wrt_instruction_second_var() {
+        let code = "WRITE_VAR x\nWRITE_VAR y";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions.len(), 2);
+        assert_eq!(bytecode.instructions[0].to_string(), "0x02 0x00");
+        assert_eq!(bytecode.instructions[1].to_string(), "0x02 0x01");
+    }
+
+    #[test]
+    fn add_wrt_invalid_var_instruction() {
+        let invalid_var = "%bad-var";
+        let code = format!("WRITE_VAR {}", invalid_var);
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err(format!(
+                "Transpilation error at line 1: invalid variable name {}",
+                invalid_var
+            ))
+        );
+    }
+
+    #[test]
+    fn add_wrt_invalid_arg_num_instruction() {
+        let code = "WRITE_VAR x y";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: expected 1 argument, got 2".to_string())
+        );
+    }
+
+    #[test]
+    fn add_read_instruction() {
+        let code = "WRITE_VAR x\nREAD_VAR x";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions.len(), 2);
+        assert_eq!(bytecode.instructions[0].to_string(), "0x02 0x00");
+        assert_eq!(bytecode.instructions[1].to_string(), "0x03 0x00");
+    }
+
+    #[test]
+    fn add_read_undeclared_instruction() {
+        let undeclared_var = "x";
+        let code = format!("READ_VAR {}", undeclared_var);
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err(format!(
+                "Transpilation error at line 1: undeclared variable {}",
+                undeclared_var
+            ))
+        );
+    }
+
+    #[test]
+    fn add_read_invalid_var_instruction() {
+        let invalid_var = "%bad-var";
+        let code = format!("READ_VAR {}", invalid_var);
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err(format!(
+                "Transpilation error at line 1: invalid variable name {}",
+                invalid_var
+            ))
+        );
+    }
+
+    #[test]
+    fn add_read_invalid_arg_num_instruction() {
+        let code = "READ_VAR x y";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: expected 1 argument, got 2".to_string())
+        );
+    }
+
+    #[test]
+    fn add_add_instruction() {
+        let code = "ADD";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions.len(), 1);
+        assert_eq!(bytecode.instructions[0].to_string(), "0x04");
+    }
+
+    #[test]
+    fn add_add_invalid_arg_num_instruction() {
+        let code = "ADD 1";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: expected 0 arguments, got 1".to_string())
+        );
+    }
+
+    #[test]
+    fn add_mult_instruction() {
+        let code = "MULTIPLY";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions.len(), 1);
+        assert_eq!(bytecode.instructions[0].to_string(), "0x05");
+    }
+
+    #[test]
+    fn add_mult_invalid_arg_num_instruction() {
+        let code = "MULTIPLY 1";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: expected 0 arguments, got 1".to_string())
+        );
+    }
+
+    #[test]
+    fn add_rtn_instruction() {
+        let code = "RETURN_VALUE";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions.len(), 1);
+        assert_eq!(bytecode.instructions[0].to_string(), "0x06");
+    }
+
+    #[test]
+    fn add_rtn_invalid_arg_num_instruction() {
+        let code = "RETURN_VALUE 1";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: expected 0 arguments, got 1".to_string())
+        );
+    }
+
+    #[test]
+    fn add_test_gt_instruction() {
+        let code = "TEST_GT";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions.len(), 1);
+        assert_eq!(bytecode.instructions[0].to_string(), "0x07");
+    }
+
+    #[test]
+    fn add_test_gt_invalid_arg_num_instruction() {
+        let code = "TEST_GT 1";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: expected 0 arguments, got 1".to_string())
+        );
+    }
+
+    #[test]
+    fn add_test_lt_instruction() {
+        let code = "TEST_LT";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions.len(), 1);
+        assert_eq!(bytecode.instructions[0].to_string(), "0x08");
+    }
+
+    #[test]
+    fn add_test_lt_invalid_arg_num_instruction() {
+        let code = "TEST_LT 1";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: expected 0 arguments, got 1".to_string())
+        );
+    }
+
+    #[test]
+    fn add_test_eq_instruction() {
+        let code = "TEST_EQ";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions.len(), 1);
+        assert_eq!(bytecode.instructions[0].to_string(), "0x09");
+    }
+
+    #[test]
+    fn add_test_eq_invalid_arg_num_instruction() {
+        let code = "TEST_EQ 1";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: expected 0 arguments, got 1".to_string())
+        );
+    }
+
+    #[test]
+    fn add_goto_instruction() {
+        let code = "&label\nGOTO &label";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions.len(), 1);
+        assert_eq!(bytecode.instructions[0].to_string(), "0x0A 0x00");
+    }
+
+    #[test]
+    fn add_goto_undeclared_instruction() {
+        let code = "GOTO &label";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: undeclared label `&label`".to_string())
+        );
+    }
+
+    #[test]
+    fn add_goto_invalid_label_instruction() {
+        let code = "GOTO @label";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: invalid label name `@label`".to_string())
+        );
+    }
+
+    #[test]
+    fn add_goto_no_label_instruction() {
+        let code = "GOTO";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: expected 1 argument, got 0".to_string())
+        );
+    }
+
+    #[test]
+    fn add_goto_invalid_arg_num_instruction() {
+        let code = "GOTO label lb";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: expected 1 argument, got 2".to_string())
+        );
+    }
+
+    #[test]
+    fn add_invalid_label_instruction() {
+        let code = "&#label";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: unknown instruction: &#label".to_string())
+        );
+    }
+
+    #[test]
+    fn add_duplicated_label_instruction() {
+        let code = "&label\nADD\n&label\nADD";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 3: duplicated label: &label".to_string())
+        );
+    }
+
+    #[test]
+    fn add_dup_instruction() {
+        let code = "DUP";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions.len(), 1);
+        assert_eq!(bytecode.instructions[0].to_string(), "0x0B");
+    }
+
+    #[test]
+    fn add_dup_invalid_arg_num_instruction() {
+        let code = "DUP 1";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: expected 0 arguments, got 1".to_string())
+        );
+    }
+
+    #[test]
+    fn add_pop_instruction() {
+        let code = "POP";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions.len(), 1);
+        assert_eq!(bytecode.instructions[0].to_string(), "0x0C");
+    }
+
+    #[test]
+    fn add_pop_invalid_arg_num_instruction() {
+        let code = "POP 1";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: expected 0 arguments, got 1".to_string())
+        );
+    }
+
+    #[test]
+    fn add_print_instruction() {
+        let code = "PRINT";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions.len(), 1);
+        assert_eq!(bytecode.instructions[0].to_string(), "0x0D");
+    }
+
+    #[test]
+    fn add_print_invalid_arg_num_instruction() {
+        let code = "PRINT 1";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert_eq!(
             result,
-            Err("Transpilation error at line 1: undeclared variable x".to_string())
+            Err("Transpilation error at line 1: expected 0 arguments, got 1".to_string())
         );
     }
 
     #[test]
-    fn add_load_instruction() {
-        let code = "LOAD_VAL 1";
+    fn add_print_str_instruction() {
+        let code = "PRINT_STR \"x = \"\nPRINT_STR \"done\"\nPRINT_STR \"x = \"";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert!(result.is_ok());
         let bytecode = result.ok().unwrap();
-        assert_eq!(bytecode.instructions.len(), 1);
-        assert_eq!(bytecode.instructions[0].to_string(), "0x01 0x01");
+        assert_eq!(bytecode.instructions.len(), 3);
+        assert_eq!(bytecode.instructions[0].to_string(), "0x0E 0x00");
+        assert_eq!(bytecode.instructions[1].to_string(), "0x0E 0x01");
+        assert_eq!(bytecode.instructions[2].to_string(), "0x0E 0x00");
+        assert_eq!(
+            bytecode.strings,
+            vec!["x = ".to_string(), "done".to_string()]
+        );
     }
+
     #[test]
-    fn add_load_invalid_arg_num_instruction() {
-        let code = "LOAD_VAL 1 1";
+    fn add_print_str_not_a_string_instruction() {
+        let code = "PRINT_STR hello";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: expected string literal, got hello".to_string())
+        );
+    }
+
+    #[test]
+    fn add_print_str_invalid_arg_num_instruction() {
+        let code = "PRINT_STR \"a\" \"b\"";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert_eq!(
@@ -275,187 +1328,409 @@ mod tests {
     }
 
     #[test]
-    fn add_wrt_instruction() {
-        let code = "WRITE_VAR x";
+    fn add_read_input_instruction() {
+        let code = "READ_INPUT";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert!(result.is_ok());
         let bytecode = result.ok().unwrap();
         assert_eq!(bytecode.instructions.len(), 1);
-        assert_eq!(bytecode.instructions[0].to_string(), "0x02 0x00");
+        assert_eq!(bytecode.instructions[0].to_string(), "0x0F");
     }
 
     #[test]
-    fn add_wrt_instruction_second_var() {
-        let code = "WRITE_VAR x\nWRITE_VAR y";
+    fn add_read_input_invalid_arg_num_instruction() {
+        let code = "READ_INPUT x";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: expected 0 arguments, got 1".to_string())
+        );
+    }
+
+    #[test]
+    fn add_param_directive() {
+        let code = ".param n\n.param m\nREAD_VAR m\nWRITE_VAR x";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert!(result.is_ok());
         let bytecode = result.ok().unwrap();
         assert_eq!(bytecode.instructions.len(), 2);
-        assert_eq!(bytecode.instructions[0].to_string(), "0x02 0x00");
-        assert_eq!(bytecode.instructions[1].to_string(), "0x02 0x01");
+        assert_eq!(bytecode.instructions[0].to_string(), "0x03 0x01");
+        assert_eq!(bytecode.instructions[1].to_string(), "0x02 0x02");
+        assert_eq!(
+            bytecode.params,
+            vec![("n".to_string(), 0), ("m".to_string(), 1)]
+        );
+        assert_eq!(bytecode.data_size, 3);
     }
 
     #[test]
-    fn add_wrt_invalid_var_instruction() {
-        let invalid_var = "%bad-var";
-        let code = format!("WRITE_VAR {}", invalid_var);
+    fn add_scopes_reuse_slots() {
+        let code = concat!(
+            "LOAD_VAL 1 | WRITE_VAR total\n",
+            ".scope\n",
+            "LOAD_VAL 2 | WRITE_VAR a | LOAD_VAL 3 | WRITE_VAR b\n",
+            ".endscope\n",
+            ".scope\n",
+            "LOAD_VAL 4 | WRITE_VAR c\n",
+            ".scope\n",
+            "LOAD_VAL 5 | WRITE_VAR d | READ_VAR total\n",
+            ".endscope\n",
+            ".endscope\n",
+            "READ_VAR total\n",
+        );
+        let mut reader = BufReader::new(code.as_bytes());
+        let bytecode = ByteCode::do_transpile(&mut reader).unwrap();
+        assert_eq!(bytecode.instructions[3].to_string(), "0x02 0x01");
+        assert_eq!(bytecode.instructions[5].to_string(), "0x02 0x02");
+        assert_eq!(bytecode.instructions[7].to_string(), "0x02 0x01");
+        assert_eq!(bytecode.instructions[9].to_string(), "0x02 0x02");
+        assert_eq!(bytecode.instructions[11].to_string(), "0x03 0x00");
+        assert_eq!(bytecode.data_size, 3);
+
+        let unscoped = code.replace(".scope\n", "").replace(".endscope\n", "");
+        let mut reader = BufReader::new(unscoped.as_bytes());
+        let bytecode = ByteCode::do_transpile(&mut reader).unwrap();
+        assert_eq!(bytecode.data_size, 5);
+    }
+
+    #[test]
+    fn add_vars_visible_at_end() {
+        let code = concat!(
+            ".param n\n",
+            ".scope\n",
+            "LOAD_VAL 1 | WRITE_VAR tmp\n",
+            ".endscope\n",
+            "LOAD_VAL 2 | WRITE_VAR total\n",
+        );
+        let mut reader = BufReader::new(code.as_bytes());
+        let bytecode = ByteCode::do_transpile(&mut reader).unwrap();
+        assert_eq!(
+            bytecode.get_vars(),
+            &[("n".to_string(), 0), ("total".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn add_scope_hides_variables() {
+        let code = ".scope\nLOAD_VAL 1\nWRITE_VAR x\n.endscope\nREAD_VAR x";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert_eq!(
             result,
-            Err(format!(
-                "Transpilation error at line 1: invalid variable name {}",
-                invalid_var
-            ))
+            Err("Transpilation error at line 5: undeclared variable x".to_string())
         );
     }
 
     #[test]
-    fn add_wrt_invalid_arg_num_instruction() {
-        let code = "WRITE_VAR x y";
+    fn add_unbalanced_scopes() {
+        let code = "LOAD_VAL 1\n.scope\n.scope\n.endscope";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert_eq!(
             result,
-            Err("Transpilation error at line 1: expected 1 argument, got 2".to_string())
+            Err("Transpilation error at line 2: .scope is missing .endscope".to_string())
+        );
+
+        let code = "LOAD_VAL 1\n.endscope";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 2: .endscope without .scope".to_string())
+        );
+
+        let code = ".scope\n.param n\n.endscope";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err(
+                "Transpilation error at line 2: parameter n cannot be declared inside a scope"
+                    .to_string()
+            )
         );
     }
 
     #[test]
-    fn add_read_instruction() {
-        let code = "WRITE_VAR x\nREAD_VAR x";
+    fn add_param_redeclared_directive() {
+        let code = "LOAD_VAL 1\nWRITE_VAR n\n.param n";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
-        assert!(result.is_ok());
-        let bytecode = result.ok().unwrap();
-        assert_eq!(bytecode.instructions.len(), 2);
-        assert_eq!(bytecode.instructions[0].to_string(), "0x02 0x00");
-        assert_eq!(bytecode.instructions[1].to_string(), "0x03 0x00");
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 3: parameter n is already declared".to_string())
+        );
     }
 
     #[test]
-    fn add_read_undeclared_instruction() {
-        let undeclared_var = "x";
-        let code = format!("READ_VAR {}", undeclared_var);
+    fn add_param_invalid_name_directive() {
+        let code = ".param 1n";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert_eq!(
             result,
-            Err(format!(
-                "Transpilation error at line 1: undeclared variable {}",
-                undeclared_var
-            ))
+            Err("Transpilation error at line 1: invalid parameter name 1n".to_string())
         );
     }
 
     #[test]
-    fn add_read_invalid_var_instruction() {
-        let invalid_var = "%bad-var";
-        let code = format!("READ_VAR {}", invalid_var);
+    fn add_param_invalid_arg_num_directive() {
+        let code = ".param";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert_eq!(
             result,
-            Err(format!(
-                "Transpilation error at line 1: invalid variable name {}",
-                invalid_var
-            ))
+            Err("Transpilation error at line 1: expected 1 argument, got 0".to_string())
         );
     }
 
     #[test]
-    fn add_read_invalid_arg_num_instruction() {
-        let code = "READ_VAR x y";
+    fn add_unknown_directive() {
+        let code = ".nonexistent";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert_eq!(
             result,
-            Err("Transpilation error at line 1: expected 1 argument, got 2".to_string())
+            Err("Transpilation error at line 1: unknown directive: .nonexistent".to_string())
         );
     }
 
     #[test]
-    fn add_add_instruction() {
-        let code = "ADD";
+    fn add_comments() {
+        let code = concat!(
+            "; full line comment\n",
+            "# another one\n",
+            "   ; indented comment\n",
+            "LOAD_VAL 1 ; inline comment\n",
+            "WRITE_VAR x # inline comment\n",
+            "PRINT_STR \"; # |\" ; not a string\n",
+        );
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert!(result.is_ok());
         let bytecode = result.ok().unwrap();
-        assert_eq!(bytecode.instructions.len(), 1);
-        assert_eq!(bytecode.instructions[0].to_string(), "0x04");
+        assert_eq!(bytecode.instructions.len(), 3);
+        assert_eq!(bytecode.instructions[0].to_string(), "0x01 0x01");
+        assert_eq!(bytecode.instructions[1].to_string(), "0x02 0x00");
+        assert_eq!(bytecode.strings, vec!["; # |".to_string()]);
     }
 
     #[test]
-    fn add_add_invalid_arg_num_instruction() {
-        let code = "ADD 1";
+    fn add_multiple_instructions_per_line() {
+        let code = "LOAD_VAL 1 | LOAD_VAL 2 | ADD ; 1 + 2\n&loop | DUP|GOTO &loop";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions.len(), 5);
+        assert_eq!(bytecode.instructions[1].to_string(), "0x01 0x02");
+        assert_eq!(bytecode.instructions[2].to_string(), "0x04");
+        assert_eq!(bytecode.instructions[3].to_string(), "0x0B");
+        assert_eq!(bytecode.instructions[4].to_string(), "0x0A 0x03");
+    }
+
+    #[test]
+    fn add_multiple_instructions_error_line() {
+        let code = "; header\n\nLOAD_VAL 1\nLOAD_VAL 2 | READ_VAR y | ADD";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert_eq!(
             result,
-            Err("Transpilation error at line 1: expected 0 arguments, got 1".to_string())
+            Err("Transpilation error at line 4: undeclared variable y".to_string())
         );
     }
 
     #[test]
-    fn add_mult_instruction() {
-        let code = "MULTIPLY";
+    fn add_only_comments() {
+        let code = "; nothing\n# here";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(result, Err("Empty program".to_string()));
+    }
+
+    const COUNT_MACRO: &str = concat!(
+        ".macro count_to counter limit\n",
+        "LOAD_VAL 0 | WRITE_VAR %counter\n",
+        "&loop\n",
+        "READ_VAR %counter | LOAD_VAL 1 | ADD | DUP | WRITE_VAR %counter\n",
+        "LOAD_VAL %limit | TEST_GT\n",
+        "GOTO &loop\n",
+        ".endmacro\n",
+    );
+
+    #[test]
+    fn add_macro_expansions() {
+        let code = format!("{}count_to i 3\ncount_to j 0x10\n", COUNT_MACRO);
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert!(result.is_ok());
         let bytecode = result.ok().unwrap();
-        assert_eq!(bytecode.instructions.len(), 1);
-        assert_eq!(bytecode.instructions[0].to_string(), "0x05");
+        assert_eq!(bytecode.instructions.len(), 20);
+        assert_eq!(bytecode.data_size, 2);
+        assert_eq!(bytecode.instructions[7].to_string(), "0x01 0x03");
+        assert_eq!(bytecode.instructions[9].to_string(), "0x0A 0x02");
+        assert_eq!(bytecode.instructions[11].to_string(), "0x02 0x01");
+        assert_eq!(bytecode.instructions[17].to_string(), "0x01 0x10");
+        assert_eq!(bytecode.instructions[19].to_string(), "0x0A 0x0c");
     }
 
     #[test]
-    fn add_mult_invalid_arg_num_instruction() {
-        let code = "MULTIPLY 1";
+    fn add_macro_error_in_expansion() {
+        let code = format!("{}\ncount_to i RESULT\n", COUNT_MACRO);
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert_eq!(
             result,
-            Err("Transpilation error at line 1: expected 0 arguments, got 1".to_string())
+            Err(
+                "Transpilation error at line 9: in macro count_to at line 5: undefined constant RESULT"
+                    .to_string()
+            )
         );
     }
 
     #[test]
-    fn add_rtn_instruction() {
-        let code = "RETURN_VALUE";
+    fn add_nested_macro_invocation() {
+        let code = concat!(
+            ".macro push v\n",
+            "LOAD_VAL %v\n",
+            ".endmacro\n",
+            ".macro pair a b\n",
+            "push %a | push %b\n",
+            ".endmacro\n",
+            "pair 1 2\n",
+            "pair 3 x\n",
+        );
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
-        assert!(result.is_ok());
-        let bytecode = result.ok().unwrap();
-        assert_eq!(bytecode.instructions.len(), 1);
-        assert_eq!(bytecode.instructions[0].to_string(), "0x06");
+        assert_eq!(
+            result,
+            Err(concat!(
+                "Transpilation error at line 8: in macro pair at line 5: ",
+                "in macro push at line 2: undefined constant x"
+            )
+            .to_string())
+        );
     }
 
     #[test]
-    fn add_rtn_invalid_arg_num_instruction() {
-        let code = "RETURN_VALUE 1";
+    fn add_invalid_macro_definitions() {
+        for (code, error) in [
+            (
+                ".macro m\nADD\n",
+                "Transpilation error at line 1: macro m is missing .endmacro",
+            ),
+            (
+                ".macro m\n.macro n\n",
+                "Transpilation error at line 2: nested macro definitions are not supported",
+            ),
+            (
+                "ADD\n.endmacro\n",
+                "Transpilation error at line 2: .endmacro without .macro",
+            ),
+            (
+                ".macro m\nADD\n.endmacro m\n",
+                "Transpilation error at line 3: expected 0 arguments, got 1",
+            ),
+            (
+                ".macro m\n.endmacro\n.macro m\n.endmacro\n",
+                "Transpilation error at line 3: macro m is already defined",
+            ),
+            (
+                ".macro m a\nADD\n.endmacro\nm\n",
+                "Transpilation error at line 4: macro m expects 1 argument(s), got 0",
+            ),
+        ] {
+            let mut reader = BufReader::new(code.as_bytes());
+            let result = ByteCode::do_transpile(&mut reader);
+            assert_eq!(result, Err(error.to_string()));
+        }
+    }
+
+    #[test]
+    fn add_recursive_macro() {
+        let code = ".macro m\nm\n.endmacro\nm\n";
         let mut reader = BufReader::new(code.as_bytes());
-        let result = ByteCode::do_transpile(&mut reader);
+        let error = ByteCode::do_transpile(&mut reader).unwrap_err();
+        assert!(error.starts_with("Transpilation error at line 4: in macro m at line 2: "));
+        assert!(error.ends_with("macro m is nested too deeply"));
+    }
+
+    #[test]
+    fn add_too_many_instructions() {
+        let definitions = concat!(
+            ".macro a\nADD | ADD | ADD | ADD | ADD | ADD | ADD | ADD\n",
+            "ADD | ADD | ADD | ADD | ADD | ADD | ADD | ADD\n.endmacro\n",
+            ".macro b\na | a | a | a | a | a | a | a | a | a | a | a | a | a | a | a\n.endmacro\n",
+            ".macro c\nb | b | b | b | b | b | b | b | b | b | b | b | b | b | b | b\n.endmacro\n",
+        );
+        let fifteen = |name: &str| format!("{}\n", [name; 15].join(" | "));
+        let largest = format!(
+            "{}{}{}{}{}",
+            definitions,
+            fifteen("c"),
+            fifteen("b"),
+            fifteen("a"),
+            fifteen("ADD")
+        );
+        let program = ByteCode::do_transpile(&mut largest.as_bytes()).unwrap();
+        assert_eq!(program.instructions.len(), usize::from(Value::MAX));
+
+        let code = format!(
+            "{}c | c | c | c | c | c | c | c | c | c | c | c | c | c | c | c\n",
+            definitions
+        );
+        let error = ByteCode::do_transpile(&mut code.as_bytes()).unwrap_err();
+        assert!(error.starts_with("Transpilation error at line 11: in macro c at line 9: "));
+        assert!(error.ends_with("program has more than 65535 instructions"));
+    }
+
+    #[test]
+    fn add_too_many_variables() {
+        let params: String = (0..=usize::from(Value::MAX))
+            .map(|i| format!(".param p{}\n", i))
+            .collect();
         assert_eq!(
-            result,
-            Err("Transpilation error at line 1: expected 0 arguments, got 1".to_string())
+            ByteCode::do_transpile(&mut params.as_bytes()),
+            Err(
+                "Transpilation error at line 65536: program has more than 65535 variables"
+                    .to_string()
+            )
         );
     }
 
     #[test]
-    fn add_test_gt_instruction() {
-        let code = "TEST_GT";
+    fn add_stack_instructions() {
+        let code = ".const N 2\nSWAP | OVER | ROT | PICK 1 | PICK N+1 | DEPTH";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert!(result.is_ok());
         let bytecode = result.ok().unwrap();
-        assert_eq!(bytecode.instructions.len(), 1);
-        assert_eq!(bytecode.instructions[0].to_string(), "0x07");
+        assert_eq!(bytecode.instructions.len(), 6);
+        assert_eq!(bytecode.instructions[0].to_string(), "0x10");
+        assert_eq!(bytecode.instructions[1].to_string(), "0x11");
+        assert_eq!(bytecode.instructions[2].to_string(), "0x12");
+        assert_eq!(bytecode.instructions[3].to_string(), "0x13 0x01");
+        assert_eq!(bytecode.instructions[4].to_string(), "0x13 0x03");
+        assert_eq!(bytecode.instructions[5].to_string(), "0x14");
     }
 
     #[test]
-    fn add_test_gt_invalid_arg_num_instruction() {
-        let code = "TEST_GT 1";
+    fn add_pick_invalid_arg_num_instruction() {
+        let code = "PICK";
+        let mut reader = BufReader::new(code.as_bytes());
+        let result = ByteCode::do_transpile(&mut reader);
+        assert_eq!(
+            result,
+            Err("Transpilation error at line 1: expected 1 argument, got 0".to_string())
+        );
+    }
+
+    #[test]
+    fn add_swap_invalid_arg_num_instruction() {
+        let code = "SWAP 1";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert_eq!(
@@ -465,19 +1740,26 @@ mod tests {
     }
 
     #[test]
-    fn add_test_lt_instruction() {
-        let code = "TEST_LT";
+    fn add_bitwise_instructions() {
+        let code = "AND | OR | XOR | NOT | SHL | SHR";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert!(result.is_ok());
         let bytecode = result.ok().unwrap();
-        assert_eq!(bytecode.instructions.len(), 1);
-        assert_eq!(bytecode.instructions[0].to_string(), "0x08");
+        let rendered: Vec<String> = bytecode
+            .instructions
+            .iter()
+            .map(|i| i.to_string())
+            .collect();
+        assert_eq!(
+            rendered,
+            vec!["0x15", "0x16", "0x17", "0x18", "0x19", "0x1A"]
+        );
     }
 
     #[test]
-    fn add_test_lt_invalid_arg_num_instruction() {
-        let code = "TEST_LT 1";
+    fn add_shl_invalid_arg_num_instruction() {
+        let code = "SHL 3";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert_eq!(
@@ -487,19 +1769,26 @@ mod tests {
     }
 
     #[test]
-    fn add_test_eq_instruction() {
-        let code = "TEST_EQ";
+    fn add_comparison_instructions() {
+        let code = "TEST_NE | TEST_GE | TEST_LE | BOOL_AND | BOOL_OR | BOOL_NOT";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert!(result.is_ok());
         let bytecode = result.ok().unwrap();
-        assert_eq!(bytecode.instructions.len(), 1);
-        assert_eq!(bytecode.instructions[0].to_string(), "0x09");
+        let rendered: Vec<String> = bytecode
+            .instructions
+            .iter()
+            .map(|i| i.to_string())
+            .collect();
+        assert_eq!(
+            rendered,
+            vec!["0x1B", "0x1C", "0x1D", "0x1E", "0x1F", "0x20"]
+        );
     }
 
     #[test]
-    fn add_test_eq_invalid_arg_num_instruction() {
-        let code = "TEST_EQ 1";
+    fn add_bool_not_invalid_arg_num_instruction() {
+        let code = "BOOL_NOT 1";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert_eq!(
@@ -509,41 +1798,52 @@ mod tests {
     }
 
     #[test]
-    fn add_goto_instruction() {
-        let code = "&label\nGOTO &label";
+    fn add_jump_instructions() {
+        let code = "&start\nJUMP &start\nGOTO_IF_ZERO &start";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert!(result.is_ok());
         let bytecode = result.ok().unwrap();
-        assert_eq!(bytecode.instructions.len(), 1);
-        assert_eq!(bytecode.instructions[0].to_string(), "0x0A 0x00");
+        assert_eq!(bytecode.instructions[0].to_string(), "0x21 0x00");
+        assert_eq!(bytecode.instructions[1].to_string(), "0x22 0x00");
     }
 
     #[test]
-    fn add_goto_undeclared_instruction() {
-        let code = "GOTO &label";
+    fn add_forward_label_references() {
+        let code = concat!(
+            "LOAD_VAL 0\n",
+            "GOTO_IF_ZERO &else\n",
+            "LOAD_VAL 1\n",
+            "JUMP &end\n",
+            "&else\n",
+            "LOAD_VAL 2\n",
+            "GOTO &end\n",
+            "&end\n",
+        );
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
-        assert_eq!(
-            result,
-            Err("Transpilation error at line 1: undeclared label `&label`".to_string())
-        );
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions[1].to_string(), "0x22 0x04");
+        assert_eq!(bytecode.instructions[3].to_string(), "0x21 0x06");
+        assert_eq!(bytecode.instructions[5].to_string(), "0x0A 0x06");
+        assert_eq!(bytecode.verify(), Ok(()));
     }
 
     #[test]
-    fn add_goto_invalid_label_instruction() {
-        let code = "GOTO #label";
+    fn add_undeclared_forward_label() {
+        let code = "LOAD_VAL 0\nJUMP &end\nLOAD_VAL 1\n&ending";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert_eq!(
             result,
-            Err("Transpilation error at line 1: invalid label name `#label`".to_string())
+            Err("Transpilation error at line 2: undeclared label `&end`".to_string())
         );
     }
 
     #[test]
-    fn add_goto_no_label_instruction() {
-        let code = "GOTO";
+    fn add_jump_invalid_arg_num_instruction() {
+        let code = "JUMP";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert_eq!(
@@ -553,79 +1853,90 @@ mod tests {
     }
 
     #[test]
-    fn add_goto_invalid_arg_num_instruction() {
-        let code = "GOTO label lb";
+    fn add_switch_instruction() {
+        let code = concat!(
+            "&first\n",
+            "LOAD_VAL 1\n",
+            "SWITCH &first &second default &other\n",
+            "&second\n",
+            "LOAD_VAL 2\n",
+            "&other\n",
+        );
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
-        assert_eq!(
-            result,
-            Err("Transpilation error at line 1: expected 1 argument, got 2".to_string())
-        );
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions[1].to_string(), "0x23 0x00 0x02 0x03");
+        assert_eq!(bytecode.verify(), Ok(()));
     }
 
     #[test]
-    fn add_invalid_label_instruction() {
-        let code = "&#label";
+    fn add_exception_instructions() {
+        let code = "TRY &handler\nLOAD_VAL 1\nTHROW\nEND_TRY\n&handler\nRETURN_VALUE";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
-        assert_eq!(
-            result,
-            Err("Transpilation error at line 1: unknown instruction: &#label".to_string())
-        );
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        assert_eq!(bytecode.instructions[0].to_string(), "0x24 0x04");
+        assert_eq!(bytecode.instructions[2].to_string(), "0x26");
+        assert_eq!(bytecode.instructions[3].to_string(), "0x25");
+        assert_eq!(bytecode.verify(), Ok(()));
     }
 
     #[test]
-    fn add_duplicated_label_instruction() {
-        let code = "&label\nADD\n&label\nADD";
+    fn add_builtin_instructions() {
+        let code = "MIN | MAX | ABS | POW | GCD | SQRT_INT | CLAMP | RAND";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
+        assert!(result.is_ok());
+        let bytecode = result.ok().unwrap();
+        let opcodes = bytecode
+            .instructions
+            .iter()
+            .map(|instruction| instruction.to_string())
+            .collect::<Vec<_>>();
         assert_eq!(
-            result,
-            Err("Transpilation error at line 3: duplicated label: &label".to_string())
+            opcodes,
+            ["0x29", "0x2A", "0x2B", "0x2C", "0x2D", "0x2E", "0x2F", "0x30"]
         );
+        assert_eq!(bytecode.verify(), Ok(()));
     }
 
     #[test]
-    fn add_dup_instruction() {
-        let code = "DUP";
+    fn add_call_host_instruction() {
+        let code = "PRINT_STR \"price\"\nLOAD_VAL 3\nCALL_HOST price\nCALL_HOST log_metric";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert!(result.is_ok());
         let bytecode = result.ok().unwrap();
-        assert_eq!(bytecode.instructions.len(), 1);
-        assert_eq!(bytecode.instructions[0].to_string(), "0x0B");
+        assert_eq!(bytecode.instructions[2].to_string(), "0x28 0x00");
+        assert_eq!(bytecode.instructions[3].to_string(), "0x28 0x01");
+        assert_eq!(bytecode.get_strings(), ["price", "log_metric"]);
+        assert_eq!(bytecode.verify(), Ok(()));
     }
 
     #[test]
-    fn add_dup_invalid_arg_num_instruction() {
-        let code = "DUP 1";
+    fn add_call_host_invalid_name() {
+        let code = "CALL_HOST 1price";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert_eq!(
             result,
-            Err("Transpilation error at line 1: expected 0 arguments, got 1".to_string())
+            Err("Transpilation error at line 1: invalid host function name 1price".to_string())
         );
     }
 
     #[test]
-    fn add_pop_instruction() {
-        let code = "POP";
-        let mut reader = BufReader::new(code.as_bytes());
-        let result = ByteCode::do_transpile(&mut reader);
-        assert!(result.is_ok());
-        let bytecode = result.ok().unwrap();
-        assert_eq!(bytecode.instructions.len(), 1);
-        assert_eq!(bytecode.instructions[0].to_string(), "0x0C");
-    }
-
-    #[test]
-    fn add_pop_invalid_arg_num_instruction() {
-        let code = "POP 1";
+    fn add_switch_missing_default() {
+        let code = "&a\nSWITCH &a &a";
         let mut reader = BufReader::new(code.as_bytes());
         let result = ByteCode::do_transpile(&mut reader);
         assert_eq!(
             result,
-            Err("Transpilation error at line 1: expected 0 arguments, got 1".to_string())
+            Err(
+                "Transpilation error at line 2: expected `default &<label>` after the SWITCH targets"
+                    .to_string()
+            )
         );
     }
 
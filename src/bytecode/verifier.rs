@@ -0,0 +1,323 @@
+use super::instruction::Instruction;
+use super::ByteCode;
+use crate::config::{OpCodes, Value};
+
+/// Checks that every instruction has an opcode and the expected number of
+/// arguments, and that every variable address, jump target and string index
+/// is in range. Transpiled programs always pass; the check guards against
+/// bytecode loaded from elsewhere.
+pub fn verify(bytecode: &ByteCode) -> Result<(), String> {
+    for (ip, instruction) in bytecode.instructions.iter().enumerate() {
+        if let Err(e) = verify_instruction(bytecode, instruction) {
+            return Err(format!("Verification error at ip = 0x{:02x}: {}", ip, e));
+        }
+    }
+    for (name, address) in &bytecode.params {
+        if *address as usize >= bytecode.data_size {
+            return Err(format!(
+                "Verification error: parameter {} has invalid variable address 0x{:02x}",
+                name, address
+            ));
+        }
+    }
+    for (name, address) in &bytecode.vars {
+        if *address as usize >= bytecode.data_size {
+            return Err(format!(
+                "Verification error: variable {} has invalid address 0x{:02x}",
+                name, address
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn verify_instruction(bytecode: &ByteCode, instruction: &Instruction) -> Result<(), String> {
+    let opcode = match instruction.opcode {
+        Some(opcode) => opcode,
+        None => return Err("empty opcode".to_string()),
+    };
+    match opcode {
+        OpCodes::LOAD | OpCodes::PICK => expect_args(instruction, opcode, 1).map(|_| ()),
+        OpCodes::WRT | OpCodes::READ => {
+            let args = expect_args(instruction, opcode, 1)?;
+            check_address(bytecode, args[0])
+        }
+        OpCodes::GOTO | OpCodes::JUMP | OpCodes::GOTZ | OpCodes::TRY => {
+            let args = expect_args(instruction, opcode, 1)?;
+            check_target(bytecode, args[0])
+        }
+        OpCodes::SWCH => match instruction.args.as_deref() {
+            Some(targets) if !targets.is_empty() => targets
+                .iter()
+                .try_for_each(|target| check_target(bytecode, *target)),
+            _ => Err("SWITCH expects at least 1 argument, got 0".to_string()),
+        },
+        OpCodes::PRNS | OpCodes::HOST => {
+            let args = expect_args(instruction, opcode, 1)?;
+            check_string(bytecode, args[0])
+        }
+        OpCodes::ASRT => {
+            let args = expect_args(instruction, opcode, 2)?;
+            check_string(bytecode, args[0])?;
+            check_string(bytecode, args[1])
+        }
+        OpCodes::ADD
+        | OpCodes::MULT
+        | OpCodes::RTN
+        | OpCodes::TEGT
+        | OpCodes::TELT
+        | OpCodes::TEEQ
+        | OpCodes::DUP
+        | OpCodes::POP
+        | OpCodes::PRNT
+        | OpCodes::INPT
+        | OpCodes::SWAP
+        | OpCodes::OVER
+        | OpCodes::ROT
+        | OpCodes::DPTH
+        | OpCodes::AND
+        | OpCodes::OR
+        | OpCodes::XOR
+        | OpCodes::NOT
+        | OpCodes::SHL
+        | OpCodes::SHR
+        | OpCodes::TENE
+        | OpCodes::TEGE
+        | OpCodes::TELE
+        | OpCodes::BAND
+        | OpCodes::BOR
+        | OpCodes::BNOT
+        | OpCodes::ETRY
+        | OpCodes::THRW
+        | OpCodes::MIN
+        | OpCodes::MAX
+        | OpCodes::ABS
+        | OpCodes::POW
+        | OpCodes::GCD
+        | OpCodes::SQRT
+        | OpCodes::CLMP
+        | OpCodes::RAND => expect_args(instruction, opcode, 0).map(|_| ()),
+    }
+}
+
+fn expect_args(
+    instruction: &Instruction,
+    opcode: OpCodes,
+    count: usize,
+) -> Result<&[Value], String> {
+    let args = instruction.args.as_deref();
+    match args {
+        None if count == 0 => Ok(&[]),
+        Some(args) if count != 0 && args.len() == count => Ok(args),
+        _ => Err(format!(
+            "{} expects {} argument(s), got {}",
+            opcode.mnemonic(),
+            count,
+            args.map_or(0, |args| args.len())
+        )),
+    }
+}
+
+fn check_address(bytecode: &ByteCode, address: Value) -> Result<(), String> {
+    if address as usize >= bytecode.data_size {
+        return Err(format!("invalid variable address 0x{:02x}", address));
+    }
+    Ok(())
+}
+
+fn check_target(bytecode: &ByteCode, target: Value) -> Result<(), String> {
+    // A label placed after the last instruction is a valid way to end the program.
+    if target as usize > bytecode.instructions.len() {
+        return Err(format!("jump target 0x{:02x} is out of range", target));
+    }
+    Ok(())
+}
+
+fn check_string(bytecode: &ByteCode, index: Value) -> Result<(), String> {
+    if index as usize >= bytecode.strings.len() {
+        return Err(format!("invalid string index 0x{:02x}", index));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytecode(instructions: Vec<Instruction>, data_size: usize) -> ByteCode {
+        ByteCode {
+            instructions,
+            data_size,
+            strings: vec!["hello".to_string()],
+            params: Vec::new(),
+            vars: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn verify_valid_program() {
+        let code = bytecode(
+            vec![
+                Instruction {
+                    opcode: Some(OpCodes::LOAD),
+                    args: Some(vec![0x1]),
+                },
+                Instruction {
+                    opcode: Some(OpCodes::WRT),
+                    args: Some(vec![0x0]),
+                },
+                Instruction {
+                    opcode: Some(OpCodes::PRNS),
+                    args: Some(vec![0x0]),
+                },
+                Instruction {
+                    opcode: Some(OpCodes::GOTO),
+                    args: Some(vec![0x4]),
+                },
+            ],
+            1,
+        );
+        assert_eq!(verify(&code), Ok(()));
+    }
+
+    #[test]
+    fn verify_empty_opcode() {
+        let code = bytecode(
+            vec![Instruction {
+                opcode: None,
+                args: None,
+            }],
+            0,
+        );
+        assert_eq!(
+            verify(&code),
+            Err("Verification error at ip = 0x00: empty opcode".to_string())
+        );
+    }
+
+    #[test]
+    fn verify_bad_arg_num() {
+        let code = bytecode(
+            vec![Instruction {
+                opcode: Some(OpCodes::ADD),
+                args: Some(vec![0x1]),
+            }],
+            0,
+        );
+        assert_eq!(
+            verify(&code),
+            Err("Verification error at ip = 0x00: ADD expects 0 argument(s), got 1".to_string())
+        );
+    }
+
+    #[test]
+    fn verify_bad_variable_address() {
+        let code = bytecode(
+            vec![Instruction {
+                opcode: Some(OpCodes::READ),
+                args: Some(vec![0x1]),
+            }],
+            1,
+        );
+        assert_eq!(
+            verify(&code),
+            Err("Verification error at ip = 0x00: invalid variable address 0x01".to_string())
+        );
+    }
+
+    #[test]
+    fn verify_bad_jump_target() {
+        let code = bytecode(
+            vec![Instruction {
+                opcode: Some(OpCodes::GOTO),
+                args: Some(vec![0x2]),
+            }],
+            0,
+        );
+        assert_eq!(
+            verify(&code),
+            Err("Verification error at ip = 0x00: jump target 0x02 is out of range".to_string())
+        );
+    }
+
+    #[test]
+    fn verify_bad_jump_if_zero_target() {
+        let code = bytecode(
+            vec![
+                Instruction {
+                    opcode: Some(OpCodes::JUMP),
+                    args: Some(vec![0x2]),
+                },
+                Instruction {
+                    opcode: Some(OpCodes::GOTZ),
+                    args: Some(vec![0x3]),
+                },
+            ],
+            0,
+        );
+        assert_eq!(
+            verify(&code),
+            Err("Verification error at ip = 0x01: jump target 0x03 is out of range".to_string())
+        );
+    }
+
+    #[test]
+    fn verify_bad_switch_target() {
+        let code = bytecode(
+            vec![Instruction {
+                opcode: Some(OpCodes::SWCH),
+                args: Some(vec![0x0, 0x5, 0x1]),
+            }],
+            0,
+        );
+        assert_eq!(
+            verify(&code),
+            Err("Verification error at ip = 0x00: jump target 0x05 is out of range".to_string())
+        );
+        let code = bytecode(
+            vec![Instruction {
+                opcode: Some(OpCodes::SWCH),
+                args: None,
+            }],
+            0,
+        );
+        assert_eq!(
+            verify(&code),
+            Err(
+                "Verification error at ip = 0x00: SWITCH expects at least 1 argument, got 0"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn verify_bad_string_index() {
+        let code = bytecode(
+            vec![Instruction {
+                opcode: Some(OpCodes::PRNS),
+                args: Some(vec![0x1]),
+            }],
+            0,
+        );
+        assert_eq!(
+            verify(&code),
+            Err("Verification error at ip = 0x00: invalid string index 0x01".to_string())
+        );
+    }
+
+    #[test]
+    fn verify_bad_param_address() {
+        let mut code = bytecode(
+            vec![Instruction {
+                opcode: Some(OpCodes::POP),
+                args: None,
+            }],
+            0,
+        );
+        code.params = vec![("n".to_string(), 0)];
+        assert_eq!(
+            verify(&code),
+            Err("Verification error: parameter n has invalid variable address 0x00".to_string())
+        );
+    }
+}
@@ -1,5 +1,6 @@
 pub type Value = u16;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum OpCodes {
     LOAD = 0x01,
@@ -14,6 +15,161 @@ pub enum OpCodes {
     GOTO = 0x0A,
     DUP = 0x0B,
     POP = 0x0C,
+    PRNT = 0x0D,
+    PRNS = 0x0E,
+    INPT = 0x0F,
+    SWAP = 0x10,
+    OVER = 0x11,
+    ROT = 0x12,
+    PICK = 0x13,
+    DPTH = 0x14,
+    AND = 0x15,
+    OR = 0x16,
+    XOR = 0x17,
+    NOT = 0x18,
+    SHL = 0x19,
+    SHR = 0x1A,
+    TENE = 0x1B,
+    TEGE = 0x1C,
+    TELE = 0x1D,
+    BAND = 0x1E,
+    BOR = 0x1F,
+    BNOT = 0x20,
+    JUMP = 0x21,
+    GOTZ = 0x22,
+    SWCH = 0x23,
+    TRY = 0x24,
+    ETRY = 0x25,
+    THRW = 0x26,
+    ASRT = 0x27,
+    HOST = 0x28,
+    MIN = 0x29,
+    MAX = 0x2A,
+    ABS = 0x2B,
+    POW = 0x2C,
+    GCD = 0x2D,
+    SQRT = 0x2E,
+    CLMP = 0x2F,
+    RAND = 0x30,
+}
+
+impl OpCodes {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            OpCodes::LOAD => "LOAD_VAL",
+            OpCodes::WRT => "WRITE_VAR",
+            OpCodes::READ => "READ_VAR",
+            OpCodes::ADD => "ADD",
+            OpCodes::MULT => "MULTIPLY",
+            OpCodes::RTN => "RETURN_VALUE",
+            OpCodes::TEGT => "TEST_GT",
+            OpCodes::TELT => "TEST_LT",
+            OpCodes::TEEQ => "TEST_EQ",
+            OpCodes::GOTO => "GOTO",
+            OpCodes::DUP => "DUP",
+            OpCodes::POP => "POP",
+            OpCodes::PRNT => "PRINT",
+            OpCodes::PRNS => "PRINT_STR",
+            OpCodes::INPT => "READ_INPUT",
+            OpCodes::SWAP => "SWAP",
+            OpCodes::OVER => "OVER",
+            OpCodes::ROT => "ROT",
+            OpCodes::PICK => "PICK",
+            OpCodes::DPTH => "DEPTH",
+            OpCodes::AND => "AND",
+            OpCodes::OR => "OR",
+            OpCodes::XOR => "XOR",
+            OpCodes::NOT => "NOT",
+            OpCodes::SHL => "SHL",
+            OpCodes::SHR => "SHR",
+            OpCodes::TENE => "TEST_NE",
+            OpCodes::TEGE => "TEST_GE",
+            OpCodes::TELE => "TEST_LE",
+            OpCodes::BAND => "BOOL_AND",
+            OpCodes::BOR => "BOOL_OR",
+            OpCodes::BNOT => "BOOL_NOT",
+            OpCodes::JUMP => "JUMP",
+            OpCodes::GOTZ => "GOTO_IF_ZERO",
+            OpCodes::SWCH => "SWITCH",
+            OpCodes::TRY => "TRY",
+            OpCodes::ETRY => "END_TRY",
+            OpCodes::THRW => "THROW",
+            OpCodes::ASRT => "ASSERT",
+            OpCodes::HOST => "CALL_HOST",
+            OpCodes::MIN => "MIN",
+            OpCodes::MAX => "MAX",
+            OpCodes::ABS => "ABS",
+            OpCodes::POW => "POW",
+            OpCodes::GCD => "GCD",
+            OpCodes::SQRT => "SQRT_INT",
+            OpCodes::CLMP => "CLAMP",
+            OpCodes::RAND => "RAND",
+        }
+    }
+
+    pub fn from_mnemonic(name: &str) -> Option<Self> {
+        (0..=u8::MAX)
+            .filter_map(|byte| OpCodes::try_from(byte).ok())
+            .find(|opcode| opcode.mnemonic() == name)
+    }
+}
+
+impl TryFrom<u8> for OpCodes {
+    type Error = String;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        match byte {
+            0x01 => Ok(OpCodes::LOAD),
+            0x02 => Ok(OpCodes::WRT),
+            0x03 => Ok(OpCodes::READ),
+            0x04 => Ok(OpCodes::ADD),
+            0x05 => Ok(OpCodes::MULT),
+            0x06 => Ok(OpCodes::RTN),
+            0x07 => Ok(OpCodes::TEGT),
+            0x08 => Ok(OpCodes::TELT),
+            0x09 => Ok(OpCodes::TEEQ),
+            0x0A => Ok(OpCodes::GOTO),
+            0x0B => Ok(OpCodes::DUP),
+            0x0C => Ok(OpCodes::POP),
+            0x0D => Ok(OpCodes::PRNT),
+            0x0E => Ok(OpCodes::PRNS),
+            0x0F => Ok(OpCodes::INPT),
+            0x10 => Ok(OpCodes::SWAP),
+            0x11 => Ok(OpCodes::OVER),
+            0x12 => Ok(OpCodes::ROT),
+            0x13 => Ok(OpCodes::PICK),
+            0x14 => Ok(OpCodes::DPTH),
+            0x15 => Ok(OpCodes::AND),
+            0x16 => Ok(OpCodes::OR),
+            0x17 => Ok(OpCodes::XOR),
+            0x18 => Ok(OpCodes::NOT),
+            0x19 => Ok(OpCodes::SHL),
+            0x1A => Ok(OpCodes::SHR),
+            0x1B => Ok(OpCodes::TENE),
+            0x1C => Ok(OpCodes::TEGE),
+            0x1D => Ok(OpCodes::TELE),
+            0x1E => Ok(OpCodes::BAND),
+            0x1F => Ok(OpCodes::BOR),
+            0x20 => Ok(OpCodes::BNOT),
+            0x21 => Ok(OpCodes::JUMP),
+            0x22 => Ok(OpCodes::GOTZ),
+            0x23 => Ok(OpCodes::SWCH),
+            0x24 => Ok(OpCodes::TRY),
+            0x25 => Ok(OpCodes::ETRY),
+            0x26 => Ok(OpCodes::THRW),
+            0x27 => Ok(OpCodes::ASRT),
+            0x28 => Ok(OpCodes::HOST),
+            0x29 => Ok(OpCodes::MIN),
+            0x2A => Ok(OpCodes::MAX),
+            0x2B => Ok(OpCodes::ABS),
+            0x2C => Ok(OpCodes::POW),
+            0x2D => Ok(OpCodes::GCD),
+            0x2E => Ok(OpCodes::SQRT),
+            0x2F => Ok(OpCodes::CLMP),
+            0x30 => Ok(OpCodes::RAND),
+            other => Err(format!("unknown opcode 0x{:02X}", other)),
+        }
+    }
 }
 
 pub trait Visitor<T> {
@@ -53,4 +209,112 @@ pub trait Visitor<T> {
     fn visit_pop(&mut self, ctx: &mut T) -> Result<(), String>
     where
         Self: Sized;
+    fn visit_print(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_print_str(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_read_input(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_swap(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_over(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_rot(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_pick(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_depth(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_and(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_or(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_xor(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_not(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_shl(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_shr(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_test_ne(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_test_ge(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_test_le(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_bool_and(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_bool_or(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_bool_not(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_jump(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_goto_if_zero(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_switch(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_try(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_end_try(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_throw(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_assert(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_call_host(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_min(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_max(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_abs(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_pow(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_gcd(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_sqrt_int(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_clamp(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
+    fn visit_rand(&mut self, ctx: &mut T) -> Result<(), String>
+    where
+        Self: Sized;
 }
@@ -0,0 +1,356 @@
+use crate::bytecode::lexer;
+use crate::config::OpCodes;
+
+const INDENT: &str = "    ";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    Blank,
+    Comment(String),
+    Label(Vec<String>, Option<String>),
+    Directive(Vec<String>, Option<String>),
+    Instruction(Vec<String>, Option<String>),
+    /// Several statements separated by `|`.
+    Compound(Vec<Vec<String>>, Option<String>),
+}
+
+fn normalize(mut tokens: Vec<String>) -> Vec<String> {
+    if let Some(head) = tokens.first_mut() {
+        let upper = head.to_uppercase();
+        // Macro names never match a mnemonic in any case, so macro
+        // invocations are left alone.
+        if OpCodes::from_mnemonic(&upper).is_some() {
+            *head = upper;
+        }
+    }
+    tokens
+}
+
+fn parse_line(line: &str) -> Result<Line, String> {
+    let (code, comment) = lexer::split_comment(line);
+    let comment = comment.map(|c| c.trim_end().to_string());
+    let statements = lexer::split_statements(code);
+    if statements.len() > 1 {
+        let mut tokens = Vec::new();
+        for statement in statements {
+            let statement = lexer::tokenize(statement)?;
+            if !statement.is_empty() {
+                tokens.push(normalize(statement));
+            }
+        }
+        if tokens.len() > 1 {
+            return Ok(Line::Compound(tokens, comment));
+        }
+        return Ok(match tokens.pop() {
+            Some(tokens) => classify(tokens, comment),
+            None => comment.map_or(Line::Blank, Line::Comment),
+        });
+    }
+    let tokens = lexer::tokenize(code)?;
+    if tokens.is_empty() {
+        return Ok(comment.map_or(Line::Blank, Line::Comment));
+    }
+    Ok(classify(tokens, comment))
+}
+
+fn classify(tokens: Vec<String>, comment: Option<String>) -> Line {
+    if tokens[0].starts_with('&') {
+        Line::Label(tokens, comment)
+    } else if tokens[0].starts_with('.') {
+        Line::Directive(tokens, comment)
+    } else {
+        Line::Instruction(normalize(tokens), comment)
+    }
+}
+
+fn with_comment(code: String, comment: &Option<String>) -> String {
+    match comment {
+        Some(comment) if code.trim().is_empty() => format!("{}{}", code, comment),
+        Some(comment) => format!("{} {}", code, comment),
+        None => code,
+    }
+}
+
+/// Formats Morango source. Labels and directives start at the first column,
+/// instructions that follow a label are indented, operands are aligned in a
+/// single column and mnemonics are upper-cased. Comments are kept and indented
+/// like the code that follows them; runs of blank lines are collapsed.
+///
+/// Formatting never changes the meaning of a program and formatting an
+/// already formatted source returns it unchanged. The bytecode only differs
+/// in the source lines recorded by ASSERT, as collapsing blank lines moves
+/// them.
+pub fn format_source(source: &str) -> Result<String, String> {
+    let mut lines = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        match parse_line(line) {
+            Ok(line) => lines.push(line),
+            Err(e) => return Err(format!("Formatting error at line {}: {}", index + 1, e)),
+        }
+    }
+    let mnemonic_width = lines
+        .iter()
+        .filter_map(|line| match line {
+            Line::Instruction(tokens, _) if tokens.len() > 1 => Some(tokens[0].len()),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+
+    let mut indents = vec![""; lines.len()];
+    let mut indent = "";
+    for (index, line) in lines.iter().enumerate() {
+        match line {
+            Line::Label(..) | Line::Directive(..) => indents[index] = "",
+            Line::Instruction(..) => indents[index] = indent,
+            Line::Compound(statements, _) => match statements[0][0].chars().next() {
+                Some('&') | Some('.') => indents[index] = "",
+                _ => indents[index] = indent,
+            },
+            Line::Blank | Line::Comment(_) => continue,
+        }
+        let declares_label = match line {
+            Line::Label(..) => true,
+            Line::Compound(statements, _) => statements.iter().any(|s| s[0].starts_with('&')),
+            _ => false,
+        };
+        if declares_label {
+            indent = INDENT;
+        }
+    }
+    // Comments are indented like the code below them.
+    let mut next_indent = "";
+    for index in (0..lines.len()).rev() {
+        match lines[index] {
+            Line::Comment(_) => indents[index] = next_indent,
+            Line::Blank => {}
+            _ => next_indent = indents[index],
+        }
+    }
+
+    let mut out = String::new();
+    let mut previous_blank = true;
+    for (line, indent) in lines.iter().zip(indents) {
+        let formatted = match line {
+            Line::Blank => {
+                if !previous_blank {
+                    out.push('\n');
+                }
+                previous_blank = true;
+                continue;
+            }
+            Line::Comment(comment) => format!("{}{}", indent, comment),
+            Line::Label(tokens, comment) => with_comment(tokens.join(" "), comment),
+            Line::Directive(tokens, comment) => with_comment(tokens.join(" "), comment),
+            Line::Instruction(tokens, comment) => {
+                let code = match tokens.split_first() {
+                    Some((mnemonic, operands)) if !operands.is_empty() => format!(
+                        "{}{:width$} {}",
+                        indent,
+                        mnemonic,
+                        operands.join(" "),
+                        width = mnemonic_width
+                    ),
+                    _ => format!("{}{}", indent, tokens[0]),
+                };
+                with_comment(code, comment)
+            }
+            Line::Compound(statements, comment) => {
+                let statements: Vec<String> = statements.iter().map(|s| s.join(" ")).collect();
+                with_comment(format!("{}{}", indent, statements.join(" | ")), comment)
+            }
+        };
+        out.push_str(&formatted);
+        out.push('\n');
+        previous_blank = false;
+    }
+    if previous_blank && out.ends_with("\n\n") {
+        out.pop();
+    }
+    Ok(out)
+}
+
+/// Returns `true` if `format_source` would leave the source unchanged.
+pub fn is_formatted(source: &str) -> Result<bool, String> {
+    Ok(format_source(source)? == source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::ByteCode;
+
+    fn transpile(source: &str) -> Result<ByteCode, String> {
+        ByteCode::transpile_reader(&mut source.as_bytes())
+    }
+
+    const NESTED_LOOP: &str = concat!(
+        "load_val 20\n",
+        "  WRITE_VAR   x\n",
+        "LOAD_VAL 0\n",
+        "WRITE_VAR y\n",
+        "\n",
+        "\n",
+        "\n",
+        "LOAD_VAL 0\n",
+        "&outer\n",
+        "LOAD_VAL 1\n",
+        "READ_VAR x\n",
+        "add\n",
+        "WRITE_VAR x\n",
+        "LOAD_VAL 1\n",
+        "   &inner\n",
+        "\tLOAD_VAL 1\n",
+        "READ_VAR y\n",
+        "ADD\n",
+        "WRITE_VAR y\n",
+        "LOAD_VAL 1\n",
+        "ADD\n",
+        "DUP\n",
+        "LOAD_VAL 3\n",
+        "TEST_GT\n",
+        "GOTO &inner\n",
+        "POP\n",
+        "LOAD_VAL 1\n",
+        "ADD\n",
+        "DUP\n",
+        "LOAD_VAL 10\n",
+        "TEST_GT\n",
+        "GOTO     &outer\n",
+        "READ_VAR x\n",
+        "READ_VAR y\n",
+        "MULTIPLY\n",
+        "RETURN_VALUE\n",
+        "\n",
+    );
+
+    #[test]
+    fn format_layout() {
+        let source = concat!(
+            "; computes 2 * 3\n",
+            ".param   n\n",
+            "load_val 2\n",
+            "\n",
+            "\n",
+            "&start ; entry\n",
+            "  ; the factor\n",
+            "LOAD_VAL 3   # three\n",
+            "multiply\n",
+            "print_str   \"a ; b\"\n",
+            "RETURN_VALUE\n",
+            "\n",
+        );
+        assert_eq!(
+            format_source(source),
+            Ok(concat!(
+                "; computes 2 * 3\n",
+                ".param n\n",
+                "LOAD_VAL  2\n",
+                "\n",
+                "&start ; entry\n",
+                "    ; the factor\n",
+                "    LOAD_VAL  3 # three\n",
+                "    MULTIPLY\n",
+                "    PRINT_STR \"a ; b\"\n",
+                "    RETURN_VALUE\n",
+            )
+            .to_string())
+        );
+    }
+
+    #[test]
+    fn format_is_idempotent() {
+        let formatted = format_source(NESTED_LOOP).unwrap();
+        assert_eq!(format_source(&formatted), Ok(formatted.clone()));
+        assert_eq!(is_formatted(&formatted), Ok(true));
+        assert_eq!(is_formatted(NESTED_LOOP), Ok(false));
+    }
+
+    #[test]
+    fn format_preserves_semantics() {
+        let original = NESTED_LOOP
+            .replace("load_val", "LOAD_VAL")
+            .replace("add", "ADD");
+        let formatted = format_source(NESTED_LOOP).unwrap();
+        assert_eq!(transpile(&formatted), transpile(&original));
+        assert!(transpile(&formatted).is_ok());
+    }
+
+    #[test]
+    fn format_test_sources_preserves_semantics() {
+        for source in [
+            include_str!("../tests/golden/simple.mor"),
+            include_str!("../tests/golden/nested_loop.mor"),
+        ] {
+            let formatted = format_source(source).unwrap();
+            assert_eq!(transpile(&formatted), transpile(source));
+            assert_eq!(format_source(&formatted), Ok(formatted));
+        }
+    }
+
+    #[test]
+    fn format_moves_assert_locations() {
+        let source = "LOAD_VAL 1\n\n\n\nASSERT \"x\"\nLOAD_VAL 0\nRETURN_VALUE\n";
+        let formatted = format_source(source).unwrap();
+        assert_eq!(
+            formatted,
+            "LOAD_VAL 1\n\nASSERT   \"x\"\nLOAD_VAL 0\nRETURN_VALUE\n"
+        );
+        let before = transpile(source).unwrap();
+        let after = transpile(&formatted).unwrap();
+        for index in 0..5 {
+            assert_eq!(after.get_instruction(index), before.get_instruction(index));
+        }
+        assert_eq!(before.get_strings(), ["x", "line 5"]);
+        assert_eq!(after.get_strings(), ["x", "line 3"]);
+    }
+
+    #[test]
+    fn format_compound_lines() {
+        let source = concat!(
+            "load_val 1|load_val   2 | add ; sum\n",
+            "&loop | dup\n",
+            "PRINT_STR \"a | b\" |\n",
+            "GOTO &loop\n",
+        );
+        let formatted = concat!(
+            "LOAD_VAL 1 | LOAD_VAL 2 | ADD ; sum\n",
+            "&loop | DUP\n",
+            "    PRINT_STR \"a | b\"\n",
+            "    GOTO      &loop\n",
+        );
+        assert_eq!(format_source(source), Ok(formatted.to_string()));
+        assert_eq!(format_source(formatted), Ok(formatted.to_string()));
+        assert_eq!(
+            transpile(formatted),
+            transpile(
+                &source
+                    .replace("load_val", "LOAD_VAL")
+                    .replace("add", "ADD")
+                    .replace("dup", "DUP")
+            )
+        );
+        assert!(transpile(formatted).is_ok());
+    }
+
+    #[test]
+    fn format_keeps_macro_names() {
+        let source = ".macro push v\nload_val %v\n.endmacro\npush 1 | push 2\nadd\n";
+        assert_eq!(
+            format_source(source),
+            Ok(".macro push v\nLOAD_VAL %v\n.endmacro\npush 1 | push 2\nADD\n".to_string())
+        );
+        let source = ".macro push v\n  LOAD_VAL %v\n.endmacro\npush 1 |push 2\nADD\nRETURN_VALUE\n";
+        let formatted = format_source(source).unwrap();
+        assert_eq!(transpile(&formatted), transpile(source));
+        assert!(transpile(&formatted).is_ok());
+    }
+
+    #[test]
+    fn format_unterminated_string() {
+        assert_eq!(
+            format_source("ADD\nPRINT_STR \"a"),
+            Err("Formatting error at line 2: unterminated string literal \"a".to_string())
+        );
+    }
+}
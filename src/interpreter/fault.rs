@@ -0,0 +1,19 @@
+//! Codes pushed on the stack when a TRY handler catches a runtime fault.
+//! Codes raised with THROW are chosen by the program and may overlap them.
+
+use crate::config::Value;
+
+/// A value was popped or picked from a stack that is too short.
+pub const STACK_UNDERFLOW: Value = 1;
+/// ADD, MULTIPLY or POW overflowed in checked mode.
+pub const OVERFLOW: Value = 2;
+/// READ_INPUT reached the end of the input or read something that is not a
+/// number.
+pub const INVALID_INPUT: Value = 3;
+/// A jump condition was neither 0 nor 1 in strict mode.
+pub const NOT_BOOLEAN: Value = 4;
+/// A function called with CALL_HOST returned an error.
+pub const HOST_ERROR: Value = 5;
+/// An operand was outside the range an instruction accepts, e.g. a RAND
+/// bound of 0.
+pub const INVALID_ARGUMENT: Value = 6;
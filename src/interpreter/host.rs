@@ -0,0 +1,19 @@
+use crate::config::Value;
+use std::fmt;
+
+pub(super) type HostFn<'a> = Box<dyn FnMut(&[Value]) -> Result<Vec<Value>, String> + 'a>;
+
+/// A Rust function that programs call with CALL_HOST.
+pub(super) struct HostFunction<'a> {
+    /// Number of values popped from the stack and passed to the function.
+    pub arity: usize,
+    pub function: HostFn<'a>,
+}
+
+impl fmt::Debug for HostFunction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostFunction")
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
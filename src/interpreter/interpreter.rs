@@ -1,20 +1,61 @@
+use super::fault;
+use super::host::{HostFn, HostFunction};
+use super::io::Io;
+use super::random::{self, Random};
+use super::report::{ExecutionReport, Termination};
+use super::snapshot::{self, Snapshot};
 use super::stack::Stack;
 use crate::bytecode::{instruction::Instruction, ByteCode};
 use crate::config::{OpCodes, Value, Visitor};
+use std::collections::HashMap;
+use std::fmt::Display;
+
+/// A TRY block being executed.
+#[derive(Debug)]
+struct Handler {
+    target: Value,
+    /// Stack depth when the block was entered, restored by the handler.
+    depth: usize,
+}
 
-#[derive(Debug, Clone)]
-struct InterpreterState {
+#[derive(Debug)]
+struct InterpreterState<'a> {
     stack: Stack<Value>,
+    max_stack_depth: usize,
     vars: Vec<Value>,
+    strings: Vec<String>,
+    io: Io<'a>,
     ip: usize,
+    strict: bool,
+    checked: bool,
+    handlers: Vec<Handler>,
+    /// Code of the fault reported by the last error, if it can be caught.
+    fault: Option<Value>,
+    hosts: HashMap<String, HostFunction<'a>>,
+    random: Random,
 }
 
-impl InterpreterState {
+impl InterpreterState<'static> {
     pub fn new(data_size: usize) -> Self {
+        InterpreterState::with_io(data_size, Io::stdio())
+    }
+}
+
+impl<'a> InterpreterState<'a> {
+    pub fn with_io(data_size: usize, io: Io<'a>) -> Self {
         let mut ret = InterpreterState {
             stack: Stack::new(),
+            max_stack_depth: 0,
             vars: vec![],
+            strings: vec![],
+            io,
             ip: 0,
+            strict: false,
+            checked: false,
+            handlers: Vec::new(),
+            fault: None,
+            hosts: HashMap::new(),
+            random: Random::new(random::DEFAULT_SEED),
         };
         ret.vars.resize(data_size, 0 as Value);
         ret
@@ -28,86 +69,491 @@ impl InterpreterState {
     pub fn next(&mut self) {
         self.ip += 1;
     }
+    pub fn error(&self, reason: impl Display) -> String {
+        format!(
+            "Runtime error: unable to process current instruction, ip = 0x{:02x}: {}",
+            self.ip, reason
+        )
+    }
+    /// Like `error`, for errors a TRY handler can catch. `code` is pushed on
+    /// the stack for the handler.
+    pub fn fault(&mut self, code: Value, reason: impl Display) -> String {
+        self.fault = Some(code);
+        self.error(reason)
+    }
+    /// Transfers control to the innermost TRY handler if the last error was a
+    /// fault, restoring the stack depth and pushing the fault code.
+    pub fn catch_fault(&mut self) -> bool {
+        let code = match self.fault.take() {
+            Some(code) => code,
+            None => return false,
+        };
+        let handler = match self.handlers.pop() {
+            Some(handler) => handler,
+            None => return false,
+        };
+        self.stack.truncate(handler.depth);
+        self.push_value(code);
+        self.set_ip(handler.target);
+        true
+    }
     pub fn pop_value(&mut self) -> Result<Value, String> {
         match self.stack.pop() {
             Some(v) => Ok(v),
-            None => Err(format!(
-                "Runtime error: unable to process current instruction, ip = 0x{:02x}: no value on stack",
-                self.ip
-            )),
+            None => Err(self.fault(fault::STACK_UNDERFLOW, "no value on stack")),
+        }
+    }
+    /// Pops the condition of a conditional jump, rejecting values other than
+    /// 0 and 1 in strict mode.
+    pub fn pop_condition(&mut self) -> Result<Value, String> {
+        let v = self.pop_value()?;
+        if self.strict && v > 1 {
+            return Err(self.fault(
+                fault::NOT_BOOLEAN,
+                format!("condition {} is not a boolean", v),
+            ));
         }
+        Ok(v)
     }
     pub fn push_value(&mut self, v: Value) {
         self.stack.push(v);
+        self.max_stack_depth = self.max_stack_depth.max(self.stack.len());
+    }
+    pub fn peek_value(&mut self, depth: Value) -> Result<Value, String> {
+        match self.stack.peek(depth as usize) {
+            Some(v) => Ok(*v),
+            None => Err(self.fault(
+                fault::STACK_UNDERFLOW,
+                format!("no value at stack depth {}", depth),
+            )),
+        }
+    }
+    pub fn depth(&self) -> Result<Value, String> {
+        match Value::try_from(self.stack.len()) {
+            Ok(depth) => Ok(depth),
+            Err(_) => Err(self.error(format!(
+                "stack depth {} does not fit in Value",
+                self.stack.len()
+            ))),
+        }
     }
     pub fn add_var(&mut self, address: Value, value: Value) -> Result<(), String> {
         if address as usize >= self.vars.len() {
-            return Err(format!(
-                "Runtime error: unable to process current instruction, ip = 0x{:02x}: invalid variable address 0x{:02x}",
-                self.ip,
-                address
-            ));
+            return Err(self.error(format!("invalid variable address 0x{:02x}", address)));
         }
         self.vars[address as usize] = value;
-        return Ok(());
+        Ok(())
     }
     pub fn read_var(&mut self, address: Value) -> Result<Value, String> {
         if address as usize >= self.vars.len() {
-            return Err(format!(
-                "Runtime error: unable to process current instruction, ip = 0x{:02x}: invalid variable address 0x{:02x}",
-                self.ip,
-                address
-            ));
+            return Err(self.error(format!("invalid variable address 0x{:02x}", address)));
         }
         Ok(self.vars[address as usize])
     }
+    pub fn get_string(&self, index: Value) -> Result<&str, String> {
+        match self.strings.get(index as usize) {
+            Some(s) => Ok(s),
+            None => Err(self.error(format!("invalid string index 0x{:02x}", index))),
+        }
+    }
+    /// Adds `v1` and `v2`, failing on overflow in checked mode and wrapping
+    /// around otherwise.
+    pub fn add(&mut self, v1: Value, v2: Value) -> Result<Value, String> {
+        if !self.checked {
+            return Ok(v1.wrapping_add(v2));
+        }
+        v1.checked_add(v2).ok_or_else(|| {
+            self.fault(
+                fault::OVERFLOW,
+                format!("arithmetic overflow in {} + {}", v2, v1),
+            )
+        })
+    }
+    /// Multiplies `v1` and `v2`, failing on overflow in checked mode and
+    /// wrapping around otherwise.
+    pub fn multiply(&mut self, v1: Value, v2: Value) -> Result<Value, String> {
+        if !self.checked {
+            return Ok(v1.wrapping_mul(v2));
+        }
+        v1.checked_mul(v2).ok_or_else(|| {
+            self.fault(
+                fault::OVERFLOW,
+                format!("arithmetic overflow in {} * {}", v2, v1),
+            )
+        })
+    }
+    /// Raises `base` to the power `exp`, failing on overflow in checked mode
+    /// and wrapping around otherwise.
+    pub fn power(&mut self, base: Value, exp: Value) -> Result<Value, String> {
+        if !self.checked {
+            return Ok(base.wrapping_pow(u32::from(exp)));
+        }
+        base.checked_pow(u32::from(exp)).ok_or_else(|| {
+            self.fault(
+                fault::OVERFLOW,
+                format!("arithmetic overflow in {} ** {}", base, exp),
+            )
+        })
+    }
+    pub fn write_line(&mut self, s: &str) -> Result<(), String> {
+        match self.io.write_line(s) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(self.error(format!("unable to write output: {}", e))),
+        }
+    }
+    pub fn read_input(&mut self) -> Result<Value, String> {
+        let line = match self.io.read_line() {
+            Ok(Some(line)) => line,
+            Ok(None) => return Err(self.fault(fault::INVALID_INPUT, "end of input")),
+            Err(e) => return Err(self.error(format!("unable to read input: {}", e))),
+        };
+        match line.trim().parse::<Value>() {
+            Ok(v) => Ok(v),
+            Err(_) => Err(self.fault(
+                fault::INVALID_INPUT,
+                format!("invalid input `{}`", line.trim()),
+            )),
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct Interpreter {
+#[derive(Debug)]
+pub struct Interpreter<'a> {
     bytecode: ByteCode,
+    state: InterpreterState<'a>,
+    params_bound: bool,
+    hosts_checked: bool,
+    steps: usize,
+    result: Option<Value>,
+    /// How the run ended, once it has.
+    termination: Option<Termination>,
 }
 
-impl Interpreter {
+impl Interpreter<'static> {
     pub fn new(bytecode: ByteCode) -> Self {
-        Interpreter { bytecode }
+        let state = InterpreterState::new(bytecode.get_data_size());
+        Interpreter::with_state(bytecode, state)
     }
-    pub fn interpret(&mut self) -> Result<Option<Value>, String> {
-        let mut ctx = InterpreterState::new(self.bytecode.get_data_size());
-        loop {
-            let mut instruction = match self.bytecode.get_instruction(ctx.get_ip()) {
-                Some(instruction) => instruction.clone(),
-                None => return Ok(None),
-            };
-            let opcode = match instruction.opcode {
-                Some(ref opcode) => opcode.clone(),
-                None => return Err("Invalid instruction: empty opcode".to_string()),
-            };
+}
 
-            match opcode {
-                OpCodes::LOAD => instruction.visit_load(&mut ctx),
-                OpCodes::WRT => instruction.visit_wrt(&mut ctx),
-                OpCodes::READ => instruction.visit_read(&mut ctx),
-                OpCodes::ADD => instruction.visit_add(&mut ctx),
-                OpCodes::MULT => instruction.visit_mult(&mut ctx),
-                OpCodes::TEGT => instruction.visit_test_gt(&mut ctx),
-                OpCodes::TELT => instruction.visit_test_lt(&mut ctx),
-                OpCodes::TEEQ => instruction.visit_test_eq(&mut ctx),
-                OpCodes::GOTO => instruction.visit_goto(&mut ctx),
-                OpCodes::DUP => instruction.visit_dup(&mut ctx),
-                OpCodes::POP => instruction.visit_pop(&mut ctx),
-                OpCodes::RTN => {
-                    instruction.visit_rtn(&mut ctx)?;
-                    return ctx.pop_value().map(Some);
+impl<'a> Interpreter<'a> {
+    pub fn with_io(bytecode: ByteCode, io: Io<'a>) -> Self {
+        let state = InterpreterState::with_io(bytecode.get_data_size(), io);
+        Interpreter::with_state(bytecode, state)
+    }
+    fn with_state(bytecode: ByteCode, mut state: InterpreterState<'a>) -> Self {
+        state.strings = bytecode.get_strings().to_vec();
+        let params_bound = bytecode.get_params().is_empty();
+        Interpreter {
+            bytecode,
+            state,
+            params_bound,
+            hosts_checked: false,
+            steps: 0,
+            result: None,
+            termination: None,
+        }
+    }
+    /// Number of instructions executed so far, including the failed one if
+    /// the execution stopped with an error.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+    /// In strict mode GOTO and GOTO_IF_ZERO fail on conditions other than 0
+    /// and 1 instead of treating any non-zero value as true.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.state.strict = strict;
+    }
+    /// In checked mode an overflowing ADD, MULTIPLY or POW is a runtime
+    /// fault, which TRY can catch. Otherwise they wrap around modulo 65536.
+    pub fn set_checked(&mut self, checked: bool) {
+        self.state.checked = checked;
+    }
+    /// Seeds the generator behind RAND. Runs with the same seed draw the
+    /// same numbers.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.state.random = Random::new(seed);
+    }
+    /// Registers a function that the program calls with `CALL_HOST <name>`.
+    /// The call pops `arity` values, passes them to `function` in the order
+    /// they were pushed and pushes the returned values. An error returned by
+    /// the function is a runtime fault. Registering a name again replaces
+    /// the previous function.
+    pub fn register_host<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: FnMut(&[Value]) -> Result<Vec<Value>, String> + 'a,
+    {
+        let function: HostFn<'a> = Box::new(function);
+        self.state
+            .hosts
+            .insert(name.to_string(), HostFunction { arity, function });
+    }
+    /// Checks that every function called with CALL_HOST is registered.
+    fn check_hosts(&self) -> Result<(), String> {
+        for ip in 0.. {
+            let instruction = match self.bytecode.get_instruction(ip) {
+                Some(instruction) => instruction,
+                None => return Ok(()),
+            };
+            if instruction.opcode != Some(OpCodes::HOST) {
+                continue;
+            }
+            let name = instruction
+                .args
+                .as_deref()
+                .and_then(|args| args.first())
+                .and_then(|index| self.state.strings.get(*index as usize));
+            if let Some(name) = name {
+                if !self.state.hosts.contains_key(name) {
+                    return Err(format!(
+                        "Verification error at ip = 0x{:02x}: unknown host function {}",
+                        ip, name
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Binds the program parameters declared with `.param`. Every declared
+    /// parameter must be given exactly once.
+    pub fn set_args(&mut self, args: &[(String, Value)]) -> Result<(), String> {
+        let params = self.bytecode.get_params();
+        for (index, (name, _)) in args.iter().enumerate() {
+            if args[..index].iter().any(|(prev, _)| prev == name) {
+                return Err(format!(
+                    "Invalid arguments: parameter {} is given more than once",
+                    name
+                ));
+            }
+            if !params.iter().any(|(param, _)| param == name) {
+                return Err(format!("Invalid arguments: unknown parameter {}", name));
+            }
+        }
+        if let Some((name, _)) = params
+            .iter()
+            .find(|(param, _)| !args.iter().any(|(name, _)| name == param))
+        {
+            return Err(format!("Invalid arguments: missing parameter {}", name));
+        }
+        for (name, value) in args {
+            let (_, address) = params.iter().find(|(param, _)| param == name).unwrap();
+            self.state.add_var(*address, *value)?;
+        }
+        self.params_bound = true;
+        Ok(())
+    }
+    /// Runs the program like `interpret` and describes how it ended, along
+    /// with the final stack and variables.
+    pub fn run(&mut self) -> ExecutionReport {
+        let _ = self.interpret();
+        let vars = self
+            .bytecode
+            .get_vars()
+            .iter()
+            .filter_map(|(name, address)| {
+                let value = self.state.vars.get(*address as usize)?;
+                Some((name.clone(), *value))
+            })
+            .collect();
+        ExecutionReport {
+            result: self.result,
+            termination: self.termination.clone().unwrap(),
+            stack: self.state.stack.stack.clone(),
+            vars,
+            steps: self.steps,
+            max_stack_depth: self.state.max_stack_depth,
+        }
+    }
+    pub fn interpret(&mut self) -> Result<Option<Value>, String> {
+        while self.step()? {}
+        match &self.termination {
+            Some(Termination::Error(e)) => Err(e.clone()),
+            _ => Ok(self.result),
+        }
+    }
+    /// Executes one instruction and returns whether the program is still
+    /// running. Once it has halted nothing is executed.
+    pub(super) fn step(&mut self) -> Result<bool, String> {
+        if self.termination.is_some() {
+            return Ok(false);
+        }
+        match self.execute() {
+            Ok(running) => Ok(running),
+            Err(e) => {
+                self.termination = Some(Termination::Error(e.clone()));
+                Err(e)
+            }
+        }
+    }
+    fn execute(&mut self) -> Result<bool, String> {
+        if !self.params_bound {
+            self.set_args(&[])?;
+        }
+        if !self.hosts_checked {
+            self.check_hosts()?;
+            self.hosts_checked = true;
+        }
+        let ctx = &mut self.state;
+        let mut instruction = match self.bytecode.get_instruction(ctx.get_ip()) {
+            Some(instruction) => instruction.clone(),
+            None => {
+                self.termination = Some(Termination::FellOffEnd);
+                return Ok(false);
+            }
+        };
+        let opcode = match instruction.opcode {
+            Some(ref opcode) => *opcode,
+            None => return Err("Invalid instruction: empty opcode".to_string()),
+        };
+        self.steps += 1;
+
+        let result = match opcode {
+            OpCodes::LOAD => instruction.visit_load(ctx),
+            OpCodes::WRT => instruction.visit_wrt(ctx),
+            OpCodes::READ => instruction.visit_read(ctx),
+            OpCodes::ADD => instruction.visit_add(ctx),
+            OpCodes::MULT => instruction.visit_mult(ctx),
+            OpCodes::TEGT => instruction.visit_test_gt(ctx),
+            OpCodes::TELT => instruction.visit_test_lt(ctx),
+            OpCodes::TEEQ => instruction.visit_test_eq(ctx),
+            OpCodes::GOTO => instruction.visit_goto(ctx),
+            OpCodes::DUP => instruction.visit_dup(ctx),
+            OpCodes::POP => instruction.visit_pop(ctx),
+            OpCodes::PRNT => instruction.visit_print(ctx),
+            OpCodes::PRNS => instruction.visit_print_str(ctx),
+            OpCodes::INPT => instruction.visit_read_input(ctx),
+            OpCodes::SWAP => instruction.visit_swap(ctx),
+            OpCodes::OVER => instruction.visit_over(ctx),
+            OpCodes::ROT => instruction.visit_rot(ctx),
+            OpCodes::PICK => instruction.visit_pick(ctx),
+            OpCodes::DPTH => instruction.visit_depth(ctx),
+            OpCodes::AND => instruction.visit_and(ctx),
+            OpCodes::OR => instruction.visit_or(ctx),
+            OpCodes::XOR => instruction.visit_xor(ctx),
+            OpCodes::NOT => instruction.visit_not(ctx),
+            OpCodes::SHL => instruction.visit_shl(ctx),
+            OpCodes::SHR => instruction.visit_shr(ctx),
+            OpCodes::TENE => instruction.visit_test_ne(ctx),
+            OpCodes::TEGE => instruction.visit_test_ge(ctx),
+            OpCodes::TELE => instruction.visit_test_le(ctx),
+            OpCodes::BAND => instruction.visit_bool_and(ctx),
+            OpCodes::BOR => instruction.visit_bool_or(ctx),
+            OpCodes::BNOT => instruction.visit_bool_not(ctx),
+            OpCodes::JUMP => instruction.visit_jump(ctx),
+            OpCodes::GOTZ => instruction.visit_goto_if_zero(ctx),
+            OpCodes::SWCH => instruction.visit_switch(ctx),
+            OpCodes::TRY => instruction.visit_try(ctx),
+            OpCodes::ETRY => instruction.visit_end_try(ctx),
+            OpCodes::THRW => instruction.visit_throw(ctx),
+            OpCodes::ASRT => instruction.visit_assert(ctx),
+            OpCodes::HOST => instruction.visit_call_host(ctx),
+            OpCodes::MIN => instruction.visit_min(ctx),
+            OpCodes::MAX => instruction.visit_max(ctx),
+            OpCodes::ABS => instruction.visit_abs(ctx),
+            OpCodes::POW => instruction.visit_pow(ctx),
+            OpCodes::GCD => instruction.visit_gcd(ctx),
+            OpCodes::SQRT => instruction.visit_sqrt_int(ctx),
+            OpCodes::CLMP => instruction.visit_clamp(ctx),
+            OpCodes::RAND => instruction.visit_rand(ctx),
+            OpCodes::RTN => match instruction.visit_rtn(ctx).and_then(|_| ctx.pop_value()) {
+                Ok(v) => {
+                    self.result = Some(v);
+                    self.termination = Some(Termination::Returned);
+                    return Ok(false);
                 }
-            }?;
+                Err(e) => Err(e),
+            },
+        };
+        if let Err(e) = result {
+            if !ctx.catch_fault() {
+                return Err(e);
+            }
+        }
+        if self.bytecode.get_instruction(ctx.get_ip()).is_none() {
+            self.termination = Some(Termination::FellOffEnd);
+            return Ok(false);
+        }
+        Ok(true)
+    }
+    pub(super) fn snapshot(&self) -> Result<Snapshot, String> {
+        Ok(Snapshot {
+            bytecode_hash: snapshot::hash_bytecode(&self.bytecode)?,
+            ip: self.state.ip,
+            steps: self.steps,
+            max_stack_depth: self.state.max_stack_depth,
+            stack: self.state.stack.stack.clone(),
+            vars: self.state.vars.clone(),
+            handlers: self
+                .state
+                .handlers
+                .iter()
+                .map(|handler| (handler.target, handler.depth))
+                .collect(),
+            random: self.state.random.state(),
+            result: self.result,
+            termination: self.termination.clone(),
+        })
+    }
+    /// Replaces the execution state with a snapshot of the same program.
+    /// The options and host functions are kept.
+    pub(super) fn restore(&mut self, snapshot: Snapshot) -> Result<(), String> {
+        if snapshot.bytecode_hash != snapshot::hash_bytecode(&self.bytecode)? {
+            return Err("Invalid snapshot: taken from a different program".to_string());
+        }
+        let running_at_valid_ip =
+            snapshot.termination.is_some() || self.bytecode.get_instruction(snapshot.ip).is_some();
+        if snapshot.vars.len() != self.state.vars.len() || !running_at_valid_ip {
+            return Err("Invalid snapshot: state does not match the program".to_string());
+        }
+        self.state.ip = snapshot.ip;
+        self.state.stack.stack = snapshot.stack;
+        self.state.max_stack_depth = snapshot.max_stack_depth;
+        self.state.vars = snapshot.vars;
+        self.state.handlers = snapshot
+            .handlers
+            .into_iter()
+            .map(|(target, depth)| Handler { target, depth })
+            .collect();
+        self.state.random = Random::new(snapshot.random);
+        self.state.fault = None;
+        self.steps = snapshot.steps;
+        self.result = snapshot.result;
+        self.termination = snapshot.termination;
+        self.params_bound = true;
+        self.hosts_checked = false;
+        Ok(())
+    }
+    pub(super) fn termination(&self) -> Option<&Termination> {
+        self.termination.as_ref()
+    }
+    pub(super) fn result(&self) -> Option<Value> {
+        self.result
+    }
+    pub(super) fn stack(&self) -> &[Value] {
+        &self.state.stack.stack
+    }
+    fn var_address(&self, name: &str) -> Option<Value> {
+        self.bytecode
+            .get_vars()
+            .iter()
+            .find(|(var, _)| var == name)
+            .map(|(_, address)| *address)
+    }
+    pub(super) fn get_var(&self, name: &str) -> Option<Value> {
+        let address = self.var_address(name)?;
+        self.state.vars.get(address as usize).copied()
+    }
+    pub(super) fn set_var(&mut self, name: &str, value: Value) -> Result<(), String> {
+        match self.var_address(name) {
+            Some(address) => self.state.add_var(address, value),
+            None => Err(format!("unknown variable {}", name)),
         }
     }
 }
 
-impl Visitor<InterpreterState> for Instruction {
-    fn visit_load(&mut self, ctx: &mut InterpreterState) -> Result<(), String> {
-        if self.args == None {
+impl Visitor<InterpreterState<'_>> for Instruction {
+    fn visit_load(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_none() {
             return Err("Invalid LOAD instruction: empty args".to_string());
         }
         if self.args.as_ref().unwrap().len() != 1 {
@@ -116,12 +562,12 @@ impl Visitor<InterpreterState> for Instruction {
                 self.args.as_ref().unwrap().len()
             ));
         }
-        ctx.push_value(self.args.as_ref().unwrap()[0].clone());
+        ctx.push_value(self.args.as_ref().unwrap()[0]);
         ctx.next();
         Ok(())
     }
-    fn visit_wrt(&mut self, ctx: &mut InterpreterState) -> Result<(), String> {
-        if self.args == None {
+    fn visit_wrt(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_none() {
             return Err("Invalid WRT instruction: empty args".to_string());
         }
         if self.args.as_ref().unwrap().len() != 1 {
@@ -131,12 +577,12 @@ impl Visitor<InterpreterState> for Instruction {
             ));
         }
         let value = ctx.pop_value()?;
-        ctx.add_var(self.args.as_ref().unwrap()[0].clone(), value)?;
+        ctx.add_var(self.args.as_ref().unwrap()[0], value)?;
         ctx.next();
         Ok(())
     }
-    fn visit_read(&mut self, ctx: &mut InterpreterState) -> Result<(), String> {
-        if self.args == None {
+    fn visit_read(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_none() {
             return Err("Invalid READ instruction: empty args".to_string());
         }
         if self.args.as_ref().unwrap().len() != 1 {
@@ -145,39 +591,41 @@ impl Visitor<InterpreterState> for Instruction {
                 self.args.as_ref().unwrap().len()
             ));
         }
-        let value = ctx.read_var(self.args.as_ref().unwrap()[0].clone())?;
+        let value = ctx.read_var(self.args.as_ref().unwrap()[0])?;
         ctx.push_value(value);
         ctx.next();
         Ok(())
     }
-    fn visit_add(&mut self, ctx: &mut InterpreterState) -> Result<(), String> {
-        if self.args != None {
+    fn visit_add(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
             return Err("Invalid ADD instruction: unexpected args".to_string());
         }
         let v1 = ctx.pop_value()?;
         let v2 = ctx.pop_value()?;
-        ctx.push_value(v1 + v2);
+        let sum = ctx.add(v1, v2)?;
+        ctx.push_value(sum);
         ctx.next();
         Ok(())
     }
-    fn visit_mult(&mut self, ctx: &mut InterpreterState) -> Result<(), String> {
-        if self.args != None {
+    fn visit_mult(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
             return Err("Invalid MULT instruction: unexpected args".to_string());
         }
         let v1 = ctx.pop_value()?;
         let v2 = ctx.pop_value()?;
-        ctx.push_value(v1 * v2);
+        let product = ctx.multiply(v1, v2)?;
+        ctx.push_value(product);
         ctx.next();
         Ok(())
     }
-    fn visit_rtn(&mut self, _ctx: &mut InterpreterState) -> Result<(), String> {
-        if self.args != None {
+    fn visit_rtn(&mut self, _ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
             return Err("Invalid RTN instruction: unexpected args".to_string());
         }
         Ok(())
     }
-    fn visit_test_eq(&mut self, ctx: &mut InterpreterState) -> Result<(), String> {
-        if self.args != None {
+    fn visit_test_eq(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
             return Err("Invalid TEEQ instruction: unexpected args".to_string());
         }
         let v1 = ctx.pop_value()?;
@@ -186,8 +634,8 @@ impl Visitor<InterpreterState> for Instruction {
         ctx.next();
         Ok(())
     }
-    fn visit_test_gt(&mut self, ctx: &mut InterpreterState) -> Result<(), String> {
-        if self.args != None {
+    fn visit_test_gt(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
             return Err("Invalid TEGT instruction: unexpected args".to_string());
         }
         let v1 = ctx.pop_value()?;
@@ -196,8 +644,8 @@ impl Visitor<InterpreterState> for Instruction {
         ctx.next();
         Ok(())
     }
-    fn visit_test_lt(&mut self, ctx: &mut InterpreterState) -> Result<(), String> {
-        if self.args != None {
+    fn visit_test_lt(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
             return Err("Invalid TELT instruction: unexpected args".to_string());
         }
         let v1 = ctx.pop_value()?;
@@ -206,8 +654,8 @@ impl Visitor<InterpreterState> for Instruction {
         ctx.next();
         Ok(())
     }
-    fn visit_goto(&mut self, ctx: &mut InterpreterState) -> Result<(), String> {
-        if self.args == None {
+    fn visit_goto(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_none() {
             return Err("Invalid GOTO instruction: empty args".to_string());
         }
         if self.args.as_ref().unwrap().len() != 1 {
@@ -216,16 +664,16 @@ impl Visitor<InterpreterState> for Instruction {
                 self.args.as_ref().unwrap().len()
             ));
         }
-        let v = ctx.pop_value()?;
+        let v = ctx.pop_condition()?;
         if v == 0 {
             ctx.next();
         } else {
-            ctx.set_ip(self.args.as_ref().unwrap()[0].clone());
+            ctx.set_ip(self.args.as_ref().unwrap()[0]);
         }
         Ok(())
     }
-    fn visit_dup(&mut self, ctx: &mut InterpreterState) -> Result<(), String> {
-        if self.args != None {
+    fn visit_dup(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
             return Err("Invalid DUP instruction: unexpected args".to_string());
         }
         let v = ctx.pop_value()?;
@@ -234,127 +682,1351 @@ impl Visitor<InterpreterState> for Instruction {
         ctx.next();
         Ok(())
     }
-    fn visit_pop(&mut self, ctx: &mut InterpreterState) -> Result<(), String> {
-        if self.args != None {
+    fn visit_pop(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
             return Err("Invalid POP instruction: unexpected args".to_string());
         }
-        ctx.pop_value()?;
-        ctx.next();
-        Ok(())
+        ctx.pop_value()?;
+        ctx.next();
+        Ok(())
+    }
+    fn visit_print(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid PRNT instruction: unexpected args".to_string());
+        }
+        let v = ctx.pop_value()?;
+        ctx.write_line(&v.to_string())?;
+        ctx.next();
+        Ok(())
+    }
+    fn visit_print_str(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_none() {
+            return Err("Invalid PRNS instruction: empty args".to_string());
+        }
+        if self.args.as_ref().unwrap().len() != 1 {
+            return Err(format!(
+                "Invalid PRNS instruction: expected 1 argument, got {}",
+                self.args.as_ref().unwrap().len()
+            ));
+        }
+        let s = ctx.get_string(self.args.as_ref().unwrap()[0])?.to_string();
+        ctx.write_line(&s)?;
+        ctx.next();
+        Ok(())
+    }
+    fn visit_read_input(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid INPT instruction: unexpected args".to_string());
+        }
+        let v = ctx.read_input()?;
+        ctx.push_value(v);
+        ctx.next();
+        Ok(())
+    }
+    fn visit_swap(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid SWAP instruction: unexpected args".to_string());
+        }
+        let v1 = ctx.pop_value()?;
+        let v2 = ctx.pop_value()?;
+        ctx.push_value(v1);
+        ctx.push_value(v2);
+        ctx.next();
+        Ok(())
+    }
+    fn visit_over(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid OVER instruction: unexpected args".to_string());
+        }
+        let v1 = ctx.pop_value()?;
+        let v2 = ctx.pop_value()?;
+        ctx.push_value(v2);
+        ctx.push_value(v1);
+        ctx.push_value(v2);
+        ctx.next();
+        Ok(())
+    }
+    fn visit_rot(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid ROT instruction: unexpected args".to_string());
+        }
+        let v1 = ctx.pop_value()?;
+        let v2 = ctx.pop_value()?;
+        let v3 = ctx.pop_value()?;
+        ctx.push_value(v2);
+        ctx.push_value(v1);
+        ctx.push_value(v3);
+        ctx.next();
+        Ok(())
+    }
+    fn visit_pick(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_none() {
+            return Err("Invalid PICK instruction: empty args".to_string());
+        }
+        if self.args.as_ref().unwrap().len() != 1 {
+            return Err(format!(
+                "Invalid PICK instruction: expected 1 argument, got {}",
+                self.args.as_ref().unwrap().len()
+            ));
+        }
+        let v = ctx.peek_value(self.args.as_ref().unwrap()[0])?;
+        ctx.push_value(v);
+        ctx.next();
+        Ok(())
+    }
+    fn visit_depth(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid DPTH instruction: unexpected args".to_string());
+        }
+        let depth = ctx.depth()?;
+        ctx.push_value(depth);
+        ctx.next();
+        Ok(())
+    }
+    fn visit_and(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid AND instruction: unexpected args".to_string());
+        }
+        let v1 = ctx.pop_value()?;
+        let v2 = ctx.pop_value()?;
+        ctx.push_value(v2 & v1);
+        ctx.next();
+        Ok(())
+    }
+    fn visit_or(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid OR instruction: unexpected args".to_string());
+        }
+        let v1 = ctx.pop_value()?;
+        let v2 = ctx.pop_value()?;
+        ctx.push_value(v2 | v1);
+        ctx.next();
+        Ok(())
+    }
+    fn visit_xor(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid XOR instruction: unexpected args".to_string());
+        }
+        let v1 = ctx.pop_value()?;
+        let v2 = ctx.pop_value()?;
+        ctx.push_value(v2 ^ v1);
+        ctx.next();
+        Ok(())
+    }
+    fn visit_not(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid NOT instruction: unexpected args".to_string());
+        }
+        let v = ctx.pop_value()?;
+        ctx.push_value(!v);
+        ctx.next();
+        Ok(())
+    }
+    fn visit_shl(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid SHL instruction: unexpected args".to_string());
+        }
+        let v1 = ctx.pop_value()?;
+        let v2 = ctx.pop_value()?;
+        ctx.push_value(v2.checked_shl(u32::from(v1)).unwrap_or(0));
+        ctx.next();
+        Ok(())
+    }
+    fn visit_shr(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid SHR instruction: unexpected args".to_string());
+        }
+        let v1 = ctx.pop_value()?;
+        let v2 = ctx.pop_value()?;
+        ctx.push_value(v2.checked_shr(u32::from(v1)).unwrap_or(0));
+        ctx.next();
+        Ok(())
+    }
+    fn visit_test_ne(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid TENE instruction: unexpected args".to_string());
+        }
+        let v1 = ctx.pop_value()?;
+        let v2 = ctx.pop_value()?;
+        ctx.push_value((v1 != v2) as Value);
+        ctx.next();
+        Ok(())
+    }
+    fn visit_test_ge(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid TEGE instruction: unexpected args".to_string());
+        }
+        let v1 = ctx.pop_value()?;
+        let v2 = ctx.pop_value()?;
+        ctx.push_value((v1 >= v2) as Value);
+        ctx.next();
+        Ok(())
+    }
+    fn visit_test_le(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid TELE instruction: unexpected args".to_string());
+        }
+        let v1 = ctx.pop_value()?;
+        let v2 = ctx.pop_value()?;
+        ctx.push_value((v1 <= v2) as Value);
+        ctx.next();
+        Ok(())
+    }
+    fn visit_bool_and(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid BAND instruction: unexpected args".to_string());
+        }
+        let v1 = ctx.pop_value()?;
+        let v2 = ctx.pop_value()?;
+        ctx.push_value((v1 != 0 && v2 != 0) as Value);
+        ctx.next();
+        Ok(())
+    }
+    fn visit_bool_or(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid BOR instruction: unexpected args".to_string());
+        }
+        let v1 = ctx.pop_value()?;
+        let v2 = ctx.pop_value()?;
+        ctx.push_value((v1 != 0 || v2 != 0) as Value);
+        ctx.next();
+        Ok(())
+    }
+    fn visit_bool_not(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid BNOT instruction: unexpected args".to_string());
+        }
+        let v = ctx.pop_value()?;
+        ctx.push_value((v == 0) as Value);
+        ctx.next();
+        Ok(())
+    }
+    fn visit_jump(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_none() {
+            return Err("Invalid JUMP instruction: empty args".to_string());
+        }
+        if self.args.as_ref().unwrap().len() != 1 {
+            return Err(format!(
+                "Invalid JUMP instruction: expected 1 argument, got {}",
+                self.args.as_ref().unwrap().len()
+            ));
+        }
+        ctx.set_ip(self.args.as_ref().unwrap()[0]);
+        Ok(())
+    }
+    fn visit_goto_if_zero(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_none() {
+            return Err("Invalid GOTZ instruction: empty args".to_string());
+        }
+        if self.args.as_ref().unwrap().len() != 1 {
+            return Err(format!(
+                "Invalid GOTZ instruction: expected 1 argument, got {}",
+                self.args.as_ref().unwrap().len()
+            ));
+        }
+        let v = ctx.pop_condition()?;
+        if v == 0 {
+            ctx.set_ip(self.args.as_ref().unwrap()[0]);
+        } else {
+            ctx.next();
+        }
+        Ok(())
+    }
+    fn visit_switch(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        let (default, targets) = match self.args.as_deref().and_then(|args| args.split_last()) {
+            Some(split) => split,
+            None => return Err("Invalid SWCH instruction: empty args".to_string()),
+        };
+        let index = ctx.pop_value()?;
+        ctx.set_ip(*targets.get(index as usize).unwrap_or(default));
+        Ok(())
+    }
+    fn visit_try(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_none() {
+            return Err("Invalid TRY instruction: empty args".to_string());
+        }
+        if self.args.as_ref().unwrap().len() != 1 {
+            return Err(format!(
+                "Invalid TRY instruction: expected 1 argument, got {}",
+                self.args.as_ref().unwrap().len()
+            ));
+        }
+        ctx.handlers.push(Handler {
+            target: self.args.as_ref().unwrap()[0],
+            depth: ctx.stack.len(),
+        });
+        ctx.next();
+        Ok(())
+    }
+    fn visit_end_try(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid ETRY instruction: unexpected args".to_string());
+        }
+        if ctx.handlers.pop().is_none() {
+            return Err(ctx.error("END_TRY without TRY"));
+        }
+        ctx.next();
+        Ok(())
+    }
+    fn visit_throw(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid THRW instruction: unexpected args".to_string());
+        }
+        let code = ctx.pop_value()?;
+        Err(ctx.fault(code, format!("uncaught exception {}", code)))
+    }
+    fn visit_assert(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_none() {
+            return Err("Invalid ASRT instruction: empty args".to_string());
+        }
+        if self.args.as_ref().unwrap().len() != 2 {
+            return Err(format!(
+                "Invalid ASRT instruction: expected 2 arguments, got {}",
+                self.args.as_ref().unwrap().len()
+            ));
+        }
+        let args = self.args.as_ref().unwrap();
+        if ctx.pop_value()? == 0 {
+            let message = ctx.get_string(args[0])?;
+            let location = ctx.get_string(args[1])?;
+            return Err(ctx.error(format!(
+                "assertion failed at {}: {}, stack: {:?}",
+                location, message, ctx.stack.stack
+            )));
+        }
+        ctx.next();
+        Ok(())
+    }
+    fn visit_call_host(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_none() {
+            return Err("Invalid HOST instruction: empty args".to_string());
+        }
+        if self.args.as_ref().unwrap().len() != 1 {
+            return Err(format!(
+                "Invalid HOST instruction: expected 1 argument, got {}",
+                self.args.as_ref().unwrap().len()
+            ));
+        }
+        let name = ctx.get_string(self.args.as_ref().unwrap()[0])?.to_string();
+        let arity = match ctx.hosts.get(&name) {
+            Some(host) => host.arity,
+            None => return Err(ctx.error(format!("unknown host function {}", name))),
+        };
+        let mut args = Vec::with_capacity(arity);
+        for _ in 0..arity {
+            args.push(ctx.pop_value()?);
+        }
+        args.reverse();
+        let results = (ctx.hosts.get_mut(&name).unwrap().function)(&args);
+        match results {
+            Ok(results) => results.into_iter().for_each(|v| ctx.push_value(v)),
+            Err(e) => {
+                return Err(ctx.fault(
+                    fault::HOST_ERROR,
+                    format!("host function {} failed: {}", name, e),
+                ))
+            }
+        }
+        ctx.next();
+        Ok(())
+    }
+    fn visit_min(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid MIN instruction: unexpected args".to_string());
+        }
+        let v1 = ctx.pop_value()?;
+        let v2 = ctx.pop_value()?;
+        ctx.push_value(v1.min(v2));
+        ctx.next();
+        Ok(())
+    }
+    fn visit_max(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid MAX instruction: unexpected args".to_string());
+        }
+        let v1 = ctx.pop_value()?;
+        let v2 = ctx.pop_value()?;
+        ctx.push_value(v1.max(v2));
+        ctx.next();
+        Ok(())
+    }
+    fn visit_abs(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid ABS instruction: unexpected args".to_string());
+        }
+        let v = ctx.pop_value()?;
+        ctx.push_value((v as i16).unsigned_abs());
+        ctx.next();
+        Ok(())
+    }
+    fn visit_pow(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid POW instruction: unexpected args".to_string());
+        }
+        let exp = ctx.pop_value()?;
+        let base = ctx.pop_value()?;
+        let v = ctx.power(base, exp)?;
+        ctx.push_value(v);
+        ctx.next();
+        Ok(())
+    }
+    fn visit_gcd(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid GCD instruction: unexpected args".to_string());
+        }
+        let mut v1 = ctx.pop_value()?;
+        let mut v2 = ctx.pop_value()?;
+        while v2 != 0 {
+            (v1, v2) = (v2, v1 % v2);
+        }
+        ctx.push_value(v1);
+        ctx.next();
+        Ok(())
+    }
+    fn visit_sqrt_int(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid SQRT instruction: unexpected args".to_string());
+        }
+        let v = ctx.pop_value()?;
+        ctx.push_value(v.isqrt());
+        ctx.next();
+        Ok(())
+    }
+    fn visit_clamp(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid CLMP instruction: unexpected args".to_string());
+        }
+        let upper = ctx.pop_value()?;
+        let lower = ctx.pop_value()?;
+        let v = ctx.pop_value()?;
+        if lower > upper {
+            return Err(ctx.fault(
+                fault::INVALID_ARGUMENT,
+                format!(
+                    "CLAMP lower bound {} is greater than upper bound {}",
+                    lower, upper
+                ),
+            ));
+        }
+        ctx.push_value(v.clamp(lower, upper));
+        ctx.next();
+        Ok(())
+    }
+    fn visit_rand(&mut self, ctx: &mut InterpreterState<'_>) -> Result<(), String> {
+        if self.args.is_some() {
+            return Err("Invalid RAND instruction: unexpected args".to_string());
+        }
+        let bound = ctx.pop_value()?;
+        if bound == 0 {
+            return Err(ctx.fault(fault::INVALID_ARGUMENT, "RAND bound must be positive"));
+        }
+        let v = ctx.random.below(bound);
+        ctx.push_value(v);
+        ctx.next();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::instruction::Instruction;
+    use crate::config::OpCodes;
+
+    #[test]
+    fn test_interpret_load() {
+        let value_to_load: Value = 0x1;
+        let mut ctx = InterpreterState::new(0);
+        let mut load_instr = Instruction {
+            opcode: Some(OpCodes::LOAD),
+            args: Some(vec![value_to_load]),
+        };
+
+        let result = load_instr.visit_load(&mut ctx);
+
+        assert!(result.is_ok());
+        let on_stack = ctx.pop_value();
+        assert!(on_stack.is_ok());
+        assert_eq!(on_stack.ok().unwrap(), value_to_load);
+        assert_eq!(ctx.ip, 0x1);
+    }
+
+    #[test]
+    fn test_interpret_bad_load_empty_args() {
+        let mut ctx = InterpreterState::new(0);
+        let mut load_instr = Instruction {
+            opcode: Some(OpCodes::LOAD),
+            args: None,
+        };
+
+        let result = load_instr.visit_load(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid LOAD instruction: empty args".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_bad_load_excessive_args() {
+        let mut ctx = InterpreterState::new(0);
+        let mut load_instr = Instruction {
+            opcode: Some(OpCodes::LOAD),
+            args: Some(vec![0x1, 0x1]),
+        };
+
+        let result = load_instr.visit_load(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid LOAD instruction: expected 1 argument, got 2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_wrt() {
+        let value_to_load: Value = 0x2;
+        let mut ctx = InterpreterState::new(1);
+        ctx.push_value(value_to_load);
+
+        let mut wrt_instr = Instruction {
+            opcode: Some(OpCodes::WRT),
+            args: Some(vec![0x0]),
+        };
+
+        let result = wrt_instr.visit_wrt(&mut ctx);
+        assert!(result.is_ok());
+        assert_eq!(ctx.vars.len(), 1);
+        assert_eq!(ctx.vars[0], value_to_load);
+        assert_eq!(ctx.ip, 0x1);
+    }
+
+    #[test]
+    fn test_interpret_bad_wrt_empty_args() {
+        let mut ctx = InterpreterState::new(0);
+        let mut wrt_instr = Instruction {
+            opcode: Some(OpCodes::WRT),
+            args: None,
+        };
+
+        let result = wrt_instr.visit_wrt(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid WRT instruction: empty args".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_bad_wrt_excessive_args() {
+        let mut ctx = InterpreterState::new(0);
+        let mut wrt_instr = Instruction {
+            opcode: Some(OpCodes::WRT),
+            args: Some(vec![0x1, 0x1]),
+        };
+
+        let result = wrt_instr.visit_wrt(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid WRT instruction: expected 1 argument, got 2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_bad_wrt_empty_stack() {
+        let mut ctx = InterpreterState::new(0);
+        let mut wrt_instr = Instruction {
+            opcode: Some(OpCodes::WRT),
+            args: Some(vec![0x2]),
+        };
+
+        let result = wrt_instr.visit_wrt(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_bad_wrt_empty_data() {
+        let value_to_load: Value = 0x2;
+        let mut ctx = InterpreterState::new(0);
+        ctx.push_value(value_to_load);
+
+        let mut wrt_instr = Instruction {
+            opcode: Some(OpCodes::WRT),
+            args: Some(vec![0x0]),
+        };
+
+        let result = wrt_instr.visit_wrt(&mut ctx);
+        assert_eq!(
+            result,
+            Err(
+                "Runtime error: unable to process current instruction, ip = 0x00: invalid variable address 0x00"
+                    .to_string(),
+            )
+        );
+    }
+
+    #[test]
+    fn test_interpret_read() {
+        let value_to_load: Value = 0x2;
+        let mut ctx = InterpreterState::new(1);
+        ctx.vars[0] = value_to_load;
+
+        let mut read_instr = Instruction {
+            opcode: Some(OpCodes::READ),
+            args: Some(vec![0x0]),
+        };
+
+        let result = read_instr.visit_read(&mut ctx);
+        assert!(result.is_ok());
+        let on_stack = ctx.pop_value();
+        assert!(on_stack.is_ok());
+        assert_eq!(on_stack.ok().unwrap(), value_to_load);
+        assert_eq!(ctx.ip, 0x1);
+    }
+
+    #[test]
+    fn test_interpret_bad_read_empty_args() {
+        let mut ctx = InterpreterState::new(0);
+        let mut read_instr = Instruction {
+            opcode: Some(OpCodes::READ),
+            args: None,
+        };
+
+        let result = read_instr.visit_read(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid READ instruction: empty args".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_bad_read_excessive_args() {
+        let mut ctx = InterpreterState::new(0);
+        let mut read_instr = Instruction {
+            opcode: Some(OpCodes::READ),
+            args: Some(vec![0x1, 0x1]),
+        };
+
+        let result = read_instr.visit_read(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid READ instruction: expected 1 argument, got 2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_bad_read_empty_data() {
+        let mut ctx = InterpreterState::new(0);
+
+        let mut read_instr = Instruction {
+            opcode: Some(OpCodes::READ),
+            args: Some(vec![0x0]),
+        };
+
+        let result = read_instr.visit_read(&mut ctx);
+        assert_eq!(
+            result,
+            Err(
+                "Runtime error: unable to process current instruction, ip = 0x00: invalid variable address 0x00"
+                    .to_string(),
+            )
+        );
+    }
+
+    #[test]
+    fn test_interpret_add() {
+        let v1: Value = 0x1;
+        let v2: Value = 0x2;
+        let mut ctx = InterpreterState::new(0);
+        ctx.push_value(v1);
+        ctx.push_value(v2);
+
+        let mut add_instr = Instruction {
+            opcode: Some(OpCodes::ADD),
+            args: None,
+        };
+
+        let result = add_instr.visit_add(&mut ctx);
+        assert!(result.is_ok());
+        let on_stack = ctx.pop_value();
+        assert!(on_stack.is_ok());
+        assert_eq!(on_stack.ok().unwrap(), v1 + v2);
+        assert_eq!(ctx.ip, 0x1);
+    }
+
+    #[test]
+    fn test_interpret_bad_add_excessive_args() {
+        let mut ctx = InterpreterState::new(0);
+        let mut add_instr = Instruction {
+            opcode: Some(OpCodes::ADD),
+            args: Some(vec![0x1, 0x1]),
+        };
+
+        let result = add_instr.visit_add(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid ADD instruction: unexpected args".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_bad_add_empty_stack() {
+        let mut ctx = InterpreterState::new(0);
+        let mut add_instr = Instruction {
+            opcode: Some(OpCodes::ADD),
+            args: None,
+        };
+
+        let result = add_instr.visit_add(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_mult() {
+        let v1: Value = 0x1;
+        let v2: Value = 0x2;
+        let mut ctx = InterpreterState::new(0);
+        ctx.push_value(v1);
+        ctx.push_value(v2);
+
+        let mut mult_instr = Instruction {
+            opcode: Some(OpCodes::MULT),
+            args: None,
+        };
+
+        let result = mult_instr.visit_mult(&mut ctx);
+        assert!(result.is_ok());
+        let on_stack = ctx.pop_value();
+        assert!(on_stack.is_ok());
+        assert_eq!(on_stack.ok().unwrap(), v1 * v2);
+        assert_eq!(ctx.ip, 0x1);
+    }
+
+    #[test]
+    fn test_interpret_bad_mult_excessive_args() {
+        let mut ctx = InterpreterState::new(0);
+        let mut mult_instr = Instruction {
+            opcode: Some(OpCodes::MULT),
+            args: Some(vec![0x1, 0x1]),
+        };
+
+        let result = mult_instr.visit_mult(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid MULT instruction: unexpected args".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_bad_mult_empty_stack() {
+        let mut ctx = InterpreterState::new(0);
+        let mut mult_instr = Instruction {
+            opcode: Some(OpCodes::MULT),
+            args: None,
+        };
+
+        let result = mult_instr.visit_mult(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_rtn() {
+        let mut ctx = InterpreterState::new(0);
+
+        let mut rtn_instr = Instruction {
+            opcode: Some(OpCodes::RTN),
+            args: None,
+        };
+
+        let result = rtn_instr.visit_rtn(&mut ctx);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_interpret_bad_rtn_excessive_args() {
+        let mut ctx = InterpreterState::new(0);
+        let mut rtn_instr = Instruction {
+            opcode: Some(OpCodes::RTN),
+            args: Some(vec![0x1]),
+        };
+
+        let result = rtn_instr.visit_rtn(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid RTN instruction: unexpected args".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_tegt() {
+        let v1: Value = 0x1;
+        let v2: Value = 0x2;
+        let mut ctx = InterpreterState::new(0);
+        ctx.push_value(v1);
+        ctx.push_value(v2);
+
+        let mut tegt_instr = Instruction {
+            opcode: Some(OpCodes::TEGT),
+            args: None,
+        };
+
+        let result = tegt_instr.visit_test_gt(&mut ctx);
+        assert!(result.is_ok());
+        let on_stack = ctx.pop_value();
+        assert!(on_stack.is_ok());
+        assert_eq!(on_stack.ok().unwrap(), 0x1);
+        assert_eq!(ctx.ip, 0x1);
+    }
+
+    #[test]
+    fn test_interpret_bad_tegt_excessive_args() {
+        let mut ctx = InterpreterState::new(0);
+        let mut tegt_instr = Instruction {
+            opcode: Some(OpCodes::TEGT),
+            args: Some(vec![0x1, 0x1]),
+        };
+
+        let result = tegt_instr.visit_test_gt(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid TEGT instruction: unexpected args".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_bad_tegt_empty_stack() {
+        let mut ctx = InterpreterState::new(0);
+        let mut tegt_instr = Instruction {
+            opcode: Some(OpCodes::TEGT),
+            args: None,
+        };
+
+        let result = tegt_instr.visit_test_gt(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_telt() {
+        let v1: Value = 0x1;
+        let v2: Value = 0x2;
+        let mut ctx = InterpreterState::new(0);
+        ctx.push_value(v1);
+        ctx.push_value(v2);
+
+        let mut telt_instr = Instruction {
+            opcode: Some(OpCodes::TELT),
+            args: None,
+        };
+
+        let result = telt_instr.visit_test_lt(&mut ctx);
+        assert!(result.is_ok());
+        let on_stack = ctx.pop_value();
+        assert!(on_stack.is_ok());
+        assert_eq!(on_stack.ok().unwrap(), 0x0);
+        assert_eq!(ctx.ip, 0x1);
+    }
+
+    #[test]
+    fn test_interpret_bad_telt_excessive_args() {
+        let mut ctx = InterpreterState::new(0);
+        let mut telt_instr = Instruction {
+            opcode: Some(OpCodes::TELT),
+            args: Some(vec![0x1, 0x1]),
+        };
+
+        let result = telt_instr.visit_test_lt(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid TELT instruction: unexpected args".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_bad_telt_empty_stack() {
+        let mut ctx = InterpreterState::new(0);
+        let mut telt_instr = Instruction {
+            opcode: Some(OpCodes::TELT),
+            args: None,
+        };
+
+        let result = telt_instr.visit_test_lt(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_teeq() {
+        let v1: Value = 0x1;
+        let v2: Value = 0x1;
+        let mut ctx = InterpreterState::new(0);
+        ctx.push_value(v1);
+        ctx.push_value(v2);
+
+        let mut teeq_instr = Instruction {
+            opcode: Some(OpCodes::TEEQ),
+            args: None,
+        };
+
+        let result = teeq_instr.visit_test_eq(&mut ctx);
+        assert!(result.is_ok());
+        let on_stack = ctx.pop_value();
+        assert!(on_stack.is_ok());
+        assert_eq!(on_stack.ok().unwrap(), 0x1);
+        assert_eq!(ctx.ip, 0x1);
+    }
+
+    #[test]
+    fn test_interpret_bad_teeq_excessive_args() {
+        let mut ctx = InterpreterState::new(0);
+        let mut teeq_instr = Instruction {
+            opcode: Some(OpCodes::TEEQ),
+            args: Some(vec![0x1, 0x1]),
+        };
+
+        let result = teeq_instr.visit_test_eq(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid TEEQ instruction: unexpected args".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_bad_teeq_empty_stack() {
+        let mut ctx = InterpreterState::new(0);
+        let mut teeq_instr = Instruction {
+            opcode: Some(OpCodes::TEEQ),
+            args: None,
+        };
+
+        let result = teeq_instr.visit_test_eq(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_goto_false() {
+        let desired_ip = 0x5;
+        let mut ctx = InterpreterState::new(0);
+        ctx.push_value(0x0);
+
+        let mut goto_instr = Instruction {
+            opcode: Some(OpCodes::GOTO),
+            args: Some(vec![desired_ip]),
+        };
+
+        let result = goto_instr.visit_goto(&mut ctx);
+        assert!(result.is_ok());
+        assert_eq!(ctx.ip, 0x1);
+    }
+
+    #[test]
+    fn test_interpret_goto_true() {
+        let desired_ip = 0x5;
+        let mut ctx = InterpreterState::new(0);
+        ctx.push_value(0x1);
+
+        let mut goto_instr = Instruction {
+            opcode: Some(OpCodes::GOTO),
+            args: Some(vec![desired_ip]),
+        };
+
+        let result = goto_instr.visit_goto(&mut ctx);
+        assert!(result.is_ok());
+        assert_eq!(ctx.ip, desired_ip as usize);
+    }
+
+    #[test]
+    fn test_interpret_goto_non_boolean() {
+        let desired_ip = 0x5;
+        let mut ctx = InterpreterState::new(0);
+        ctx.push_value(0x7);
+
+        let mut goto_instr = Instruction {
+            opcode: Some(OpCodes::GOTO),
+            args: Some(vec![desired_ip]),
+        };
+
+        let result = goto_instr.visit_goto(&mut ctx);
+        assert!(result.is_ok());
+        assert_eq!(ctx.ip, desired_ip as usize);
+    }
+
+    #[test]
+    fn test_interpret_bad_goto_strict_non_boolean() {
+        let mut ctx = InterpreterState::new(0);
+        ctx.strict = true;
+        ctx.push_value(0x7);
+
+        let mut goto_instr = Instruction {
+            opcode: Some(OpCodes::GOTO),
+            args: Some(vec![0x5]),
+        };
+
+        let result = goto_instr.visit_goto(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Runtime error: unable to process current instruction, ip = 0x00: condition 7 is not a boolean".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_bad_goto_excessive_args() {
+        let mut ctx = InterpreterState::new(0);
+        let mut goto_instr = Instruction {
+            opcode: Some(OpCodes::GOTO),
+            args: Some(vec![0x1, 0x1]),
+        };
+
+        let result = goto_instr.visit_goto(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid GOTO instruction: expected 1 argument, got 2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_bad_goto_empty_args() {
+        let mut ctx = InterpreterState::new(0);
+        let mut goto_instr = Instruction {
+            opcode: Some(OpCodes::GOTO),
+            args: None,
+        };
+
+        let result = goto_instr.visit_goto(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid GOTO instruction: empty args".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_bad_goto_empty_stack() {
+        let mut ctx = InterpreterState::new(0);
+        let mut goto_instr = Instruction {
+            opcode: Some(OpCodes::GOTO),
+            args: Some(vec![0x1]),
+        };
+
+        let result = goto_instr.visit_goto(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_dup() {
+        let v1: Value = 0x1;
+        let mut ctx = InterpreterState::new(0);
+        ctx.push_value(v1);
+
+        let mut dup_instr = Instruction {
+            opcode: Some(OpCodes::DUP),
+            args: None,
+        };
+
+        let result = dup_instr.visit_dup(&mut ctx);
+        assert!(result.is_ok());
+        let on_stack_1 = ctx.pop_value();
+        let on_stack_2 = ctx.pop_value();
+        assert!(on_stack_1.is_ok());
+        assert!(on_stack_2.is_ok());
+        assert_eq!(on_stack_1.ok().unwrap(), 0x1);
+        assert_eq!(on_stack_2.ok().unwrap(), 0x1);
+        assert_eq!(ctx.ip, 0x1);
+    }
+
+    #[test]
+    fn test_interpret_bad_dup_excessive_args() {
+        let mut ctx = InterpreterState::new(0);
+        let mut dup_instr = Instruction {
+            opcode: Some(OpCodes::DUP),
+            args: Some(vec![0x1, 0x1]),
+        };
+
+        let result = dup_instr.visit_dup(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid DUP instruction: unexpected args".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_bad_dup_empty_stack() {
+        let mut ctx = InterpreterState::new(0);
+        let mut dup_instr = Instruction {
+            opcode: Some(OpCodes::DUP),
+            args: None,
+        };
+
+        let result = dup_instr.visit_dup(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_pop() {
+        let mut ctx = InterpreterState::new(0);
+        ctx.push_value(0x2);
+
+        let mut pop_instr = Instruction {
+            opcode: Some(OpCodes::POP),
+            args: None,
+        };
+
+        let result = pop_instr.visit_pop(&mut ctx);
+        assert!(result.is_ok());
+        let on_stack = ctx.pop_value();
+        assert_eq!(on_stack, Err("Runtime error: unable to process current instruction, ip = 0x01: no value on stack".to_string()));
+        assert_eq!(ctx.ip, 0x1);
+    }
+
+    #[test]
+    fn test_interpret_bad_pop_excessive_args() {
+        let mut ctx = InterpreterState::new(0);
+        let mut pop_instr = Instruction {
+            opcode: Some(OpCodes::POP),
+            args: Some(vec![0x1, 0x1]),
+        };
+
+        let result = pop_instr.visit_pop(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid POP instruction: unexpected args".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_bad_pop_empty_stack() {
+        let mut ctx = InterpreterState::new(0);
+        let mut pop_instr = Instruction {
+            opcode: Some(OpCodes::POP),
+            args: None,
+        };
+
+        let result = pop_instr.visit_pop(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_print() {
+        let mut output = Vec::new();
+        {
+            let io = Io::new("".as_bytes(), &mut output);
+            let mut ctx = InterpreterState::with_io(0, io);
+            ctx.push_value(0x2a);
+
+            let mut print_instr = Instruction {
+                opcode: Some(OpCodes::PRNT),
+                args: None,
+            };
+
+            let result = print_instr.visit_print(&mut ctx);
+            assert!(result.is_ok());
+            assert_eq!(ctx.ip, 0x1);
+        }
+        assert_eq!(String::from_utf8(output).unwrap(), "42\n");
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::bytecode::instruction::Instruction;
-    use crate::config::OpCodes;
+    #[test]
+    fn test_interpret_bad_print_excessive_args() {
+        let mut ctx = InterpreterState::new(0);
+        let mut print_instr = Instruction {
+            opcode: Some(OpCodes::PRNT),
+            args: Some(vec![0x1]),
+        };
+
+        let result = print_instr.visit_print(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid PRNT instruction: unexpected args".to_string())
+        );
+    }
 
     #[test]
-    fn test_interpret_load() {
-        let value_to_load: Value = 0x1;
+    fn test_interpret_bad_print_empty_stack() {
         let mut ctx = InterpreterState::new(0);
-        let mut load_instr = Instruction {
-            opcode: Some(OpCodes::LOAD),
-            args: Some(vec![value_to_load]),
+        let mut print_instr = Instruction {
+            opcode: Some(OpCodes::PRNT),
+            args: None,
         };
 
-        let result = load_instr.visit_load(&mut ctx);
+        let result = print_instr.visit_print(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
+        );
+    }
 
-        assert!(result.is_ok());
-        let on_stack = ctx.pop_value();
-        assert!(on_stack.is_ok());
-        assert_eq!(on_stack.ok().unwrap(), value_to_load);
-        assert_eq!(ctx.ip, 0x1);
+    #[test]
+    fn test_interpret_print_str() {
+        let mut output = Vec::new();
+        {
+            let io = Io::new("".as_bytes(), &mut output);
+            let mut ctx = InterpreterState::with_io(0, io);
+            ctx.strings = vec!["hello".to_string()];
+
+            let mut print_str_instr = Instruction {
+                opcode: Some(OpCodes::PRNS),
+                args: Some(vec![0x0]),
+            };
+
+            let result = print_str_instr.visit_print_str(&mut ctx);
+            assert!(result.is_ok());
+            assert_eq!(ctx.ip, 0x1);
+        }
+        assert_eq!(String::from_utf8(output).unwrap(), "hello\n");
     }
 
     #[test]
-    fn test_interpret_bad_load_empty_args() {
+    fn test_interpret_bad_print_str_empty_args() {
         let mut ctx = InterpreterState::new(0);
-        let mut load_instr = Instruction {
-            opcode: Some(OpCodes::LOAD),
+        let mut print_str_instr = Instruction {
+            opcode: Some(OpCodes::PRNS),
             args: None,
         };
 
-        let result = load_instr.visit_load(&mut ctx);
+        let result = print_str_instr.visit_print_str(&mut ctx);
         assert_eq!(
             result,
-            Err("Invalid LOAD instruction: empty args".to_string())
+            Err("Invalid PRNS instruction: empty args".to_string())
         );
     }
 
     #[test]
-    fn test_interpret_bad_load_excessive_args() {
+    fn test_interpret_bad_print_str_invalid_index() {
         let mut ctx = InterpreterState::new(0);
-        let mut load_instr = Instruction {
-            opcode: Some(OpCodes::LOAD),
-            args: Some(vec![0x1, 0x1]),
+        let mut print_str_instr = Instruction {
+            opcode: Some(OpCodes::PRNS),
+            args: Some(vec![0x3]),
         };
 
-        let result = load_instr.visit_load(&mut ctx);
+        let result = print_str_instr.visit_print_str(&mut ctx);
         assert_eq!(
             result,
-            Err("Invalid LOAD instruction: expected 1 argument, got 2".to_string())
+            Err("Runtime error: unable to process current instruction, ip = 0x00: invalid string index 0x03".to_string())
         );
     }
 
     #[test]
-    fn test_interpret_wrt() {
-        let value_to_load: Value = 0x2;
-        let mut ctx = InterpreterState::new(1);
-        ctx.push_value(value_to_load);
+    fn test_interpret_read_input() {
+        let io = Io::new(" 17 \n5\n".as_bytes(), Vec::new());
+        let mut ctx = InterpreterState::with_io(0, io);
 
-        let mut wrt_instr = Instruction {
-            opcode: Some(OpCodes::WRT),
-            args: Some(vec![0x0]),
+        let mut read_input_instr = Instruction {
+            opcode: Some(OpCodes::INPT),
+            args: None,
         };
 
-        let result = wrt_instr.visit_wrt(&mut ctx);
+        let result = read_input_instr.visit_read_input(&mut ctx);
         assert!(result.is_ok());
-        assert_eq!(ctx.vars.len(), 1);
-        assert_eq!(ctx.vars[0], value_to_load);
+        assert_eq!(ctx.pop_value(), Ok(17));
         assert_eq!(ctx.ip, 0x1);
     }
 
     #[test]
-    fn test_interpret_bad_wrt_empty_args() {
+    fn test_interpret_bad_read_input_excessive_args() {
         let mut ctx = InterpreterState::new(0);
-        let mut wrt_instr = Instruction {
-            opcode: Some(OpCodes::WRT),
+        let mut read_input_instr = Instruction {
+            opcode: Some(OpCodes::INPT),
+            args: Some(vec![0x1]),
+        };
+
+        let result = read_input_instr.visit_read_input(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid INPT instruction: unexpected args".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_bad_read_input_not_a_number() {
+        let io = Io::new("abc\n".as_bytes(), Vec::new());
+        let mut ctx = InterpreterState::with_io(0, io);
+        let mut read_input_instr = Instruction {
+            opcode: Some(OpCodes::INPT),
             args: None,
         };
 
-        let result = wrt_instr.visit_wrt(&mut ctx);
+        let result = read_input_instr.visit_read_input(&mut ctx);
         assert_eq!(
             result,
-            Err("Invalid WRT instruction: empty args".to_string())
+            Err("Runtime error: unable to process current instruction, ip = 0x00: invalid input `abc`".to_string())
         );
     }
 
     #[test]
-    fn test_interpret_bad_wrt_excessive_args() {
-        let mut ctx = InterpreterState::new(0);
-        let mut wrt_instr = Instruction {
-            opcode: Some(OpCodes::WRT),
-            args: Some(vec![0x1, 0x1]),
+    fn test_interpret_bad_read_input_end_of_input() {
+        let io = Io::new("".as_bytes(), Vec::new());
+        let mut ctx = InterpreterState::with_io(0, io);
+        let mut read_input_instr = Instruction {
+            opcode: Some(OpCodes::INPT),
+            args: None,
         };
 
-        let result = wrt_instr.visit_wrt(&mut ctx);
+        let result = read_input_instr.visit_read_input(&mut ctx);
         assert_eq!(
             result,
-            Err("Invalid WRT instruction: expected 1 argument, got 2".to_string())
+            Err(
+                "Runtime error: unable to process current instruction, ip = 0x00: end of input"
+                    .to_string()
+            )
         );
     }
 
+    fn stack_of(ctx: &mut InterpreterState<'_>) -> Vec<Value> {
+        let mut values = Vec::new();
+        while let Ok(v) = ctx.pop_value() {
+            values.push(v);
+        }
+        values.reverse();
+        values
+    }
+
     #[test]
-    fn test_interpret_bad_wrt_empty_stack() {
+    fn test_interpret_swap() {
         let mut ctx = InterpreterState::new(0);
-        let mut wrt_instr = Instruction {
-            opcode: Some(OpCodes::WRT),
-            args: Some(vec![0x2]),
+        ctx.push_value(0x1);
+        ctx.push_value(0x2);
+        let mut swap_instr = Instruction {
+            opcode: Some(OpCodes::SWAP),
+            args: None,
         };
 
-        let result = wrt_instr.visit_wrt(&mut ctx);
+        let result = swap_instr.visit_swap(&mut ctx);
+        assert!(result.is_ok());
+        assert_eq!(ctx.ip, 0x1);
+        assert_eq!(stack_of(&mut ctx), vec![0x2, 0x1]);
+    }
+
+    #[test]
+    fn test_interpret_bad_swap_empty_stack() {
+        let mut ctx = InterpreterState::new(0);
+        ctx.push_value(0x1);
+        let mut swap_instr = Instruction {
+            opcode: Some(OpCodes::SWAP),
+            args: None,
+        };
+
+        let result = swap_instr.visit_swap(&mut ctx);
         assert_eq!(
             result,
             Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
@@ -362,190 +2034,214 @@ mod tests {
     }
 
     #[test]
-    fn test_interpret_bad_wrt_empty_data() {
-        let value_to_load: Value = 0x2;
+    fn test_interpret_bad_swap_excessive_args() {
         let mut ctx = InterpreterState::new(0);
-        ctx.push_value(value_to_load);
-
-        let mut wrt_instr = Instruction {
-            opcode: Some(OpCodes::WRT),
-            args: Some(vec![0x0]),
+        let mut swap_instr = Instruction {
+            opcode: Some(OpCodes::SWAP),
+            args: Some(vec![0x1]),
         };
 
-        let result = wrt_instr.visit_wrt(&mut ctx);
+        let result = swap_instr.visit_swap(&mut ctx);
         assert_eq!(
             result,
-            Err(
-                "Runtime error: unable to process current instruction, ip = 0x00: invalid variable address 0x00"
-                    .to_string(),
-            )
+            Err("Invalid SWAP instruction: unexpected args".to_string())
         );
     }
 
     #[test]
-    fn test_interpret_read() {
-        let value_to_load: Value = 0x2;
-        let mut ctx = InterpreterState::new(1);
-        ctx.vars[0] = value_to_load;
-
-        let mut read_instr = Instruction {
-            opcode: Some(OpCodes::READ),
-            args: Some(vec![0x0]),
+    fn test_interpret_over() {
+        let mut ctx = InterpreterState::new(0);
+        ctx.push_value(0x1);
+        ctx.push_value(0x2);
+        let mut over_instr = Instruction {
+            opcode: Some(OpCodes::OVER),
+            args: None,
         };
 
-        let result = read_instr.visit_read(&mut ctx);
+        let result = over_instr.visit_over(&mut ctx);
         assert!(result.is_ok());
-        let on_stack = ctx.pop_value();
-        assert!(on_stack.is_ok());
-        assert_eq!(on_stack.ok().unwrap(), value_to_load);
         assert_eq!(ctx.ip, 0x1);
+        assert_eq!(stack_of(&mut ctx), vec![0x1, 0x2, 0x1]);
     }
 
     #[test]
-    fn test_interpret_bad_read_empty_args() {
+    fn test_interpret_bad_over_empty_stack() {
         let mut ctx = InterpreterState::new(0);
-        let mut read_instr = Instruction {
-            opcode: Some(OpCodes::READ),
+        ctx.push_value(0x1);
+        let mut over_instr = Instruction {
+            opcode: Some(OpCodes::OVER),
             args: None,
         };
 
-        let result = read_instr.visit_read(&mut ctx);
+        let result = over_instr.visit_over(&mut ctx);
         assert_eq!(
             result,
-            Err("Invalid READ instruction: empty args".to_string())
+            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
         );
     }
 
     #[test]
-    fn test_interpret_bad_read_excessive_args() {
+    fn test_interpret_rot() {
         let mut ctx = InterpreterState::new(0);
-        let mut read_instr = Instruction {
-            opcode: Some(OpCodes::READ),
-            args: Some(vec![0x1, 0x1]),
+        ctx.push_value(0x1);
+        ctx.push_value(0x2);
+        ctx.push_value(0x3);
+        let mut rot_instr = Instruction {
+            opcode: Some(OpCodes::ROT),
+            args: None,
         };
 
-        let result = read_instr.visit_read(&mut ctx);
-        assert_eq!(
-            result,
-            Err("Invalid READ instruction: expected 1 argument, got 2".to_string())
-        );
+        let result = rot_instr.visit_rot(&mut ctx);
+        assert!(result.is_ok());
+        assert_eq!(ctx.ip, 0x1);
+        assert_eq!(stack_of(&mut ctx), vec![0x2, 0x3, 0x1]);
     }
 
     #[test]
-    fn test_interpret_bad_read_empty_data() {
+    fn test_interpret_bad_rot_empty_stack() {
         let mut ctx = InterpreterState::new(0);
-
-        let mut read_instr = Instruction {
-            opcode: Some(OpCodes::READ),
-            args: Some(vec![0x0]),
+        ctx.push_value(0x1);
+        ctx.push_value(0x2);
+        let mut rot_instr = Instruction {
+            opcode: Some(OpCodes::ROT),
+            args: None,
         };
 
-        let result = read_instr.visit_read(&mut ctx);
+        let result = rot_instr.visit_rot(&mut ctx);
         assert_eq!(
             result,
-            Err(
-                "Runtime error: unable to process current instruction, ip = 0x00: invalid variable address 0x00"
-                    .to_string(),
-            )
+            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
         );
     }
 
     #[test]
-    fn test_interpret_add() {
-        let v1: Value = 0x1;
-        let v2: Value = 0x2;
+    fn test_interpret_pick() {
         let mut ctx = InterpreterState::new(0);
-        ctx.push_value(v1);
-        ctx.push_value(v2);
-
-        let mut add_instr = Instruction {
-            opcode: Some(OpCodes::ADD),
-            args: None,
+        ctx.push_value(0x1);
+        ctx.push_value(0x2);
+        ctx.push_value(0x3);
+        let mut pick_instr = Instruction {
+            opcode: Some(OpCodes::PICK),
+            args: Some(vec![0x2]),
         };
 
-        let result = add_instr.visit_add(&mut ctx);
+        let result = pick_instr.visit_pick(&mut ctx);
         assert!(result.is_ok());
-        let on_stack = ctx.pop_value();
-        assert!(on_stack.is_ok());
-        assert_eq!(on_stack.ok().unwrap(), v1 + v2);
         assert_eq!(ctx.ip, 0x1);
+        assert_eq!(stack_of(&mut ctx), vec![0x1, 0x2, 0x3, 0x1]);
     }
 
     #[test]
-    fn test_interpret_bad_add_excessive_args() {
+    fn test_interpret_bad_pick_empty_stack() {
         let mut ctx = InterpreterState::new(0);
-        let mut add_instr = Instruction {
-            opcode: Some(OpCodes::ADD),
-            args: Some(vec![0x1, 0x1]),
+        ctx.push_value(0x1);
+        let mut pick_instr = Instruction {
+            opcode: Some(OpCodes::PICK),
+            args: Some(vec![0x1]),
         };
 
-        let result = add_instr.visit_add(&mut ctx);
+        let result = pick_instr.visit_pick(&mut ctx);
         assert_eq!(
             result,
-            Err("Invalid ADD instruction: unexpected args".to_string())
+            Err("Runtime error: unable to process current instruction, ip = 0x00: no value at stack depth 1".to_string())
         );
     }
 
     #[test]
-    fn test_interpret_bad_add_empty_stack() {
+    fn test_interpret_bad_pick_empty_args() {
         let mut ctx = InterpreterState::new(0);
-        let mut add_instr = Instruction {
-            opcode: Some(OpCodes::ADD),
+        let mut pick_instr = Instruction {
+            opcode: Some(OpCodes::PICK),
             args: None,
         };
 
-        let result = add_instr.visit_add(&mut ctx);
+        let result = pick_instr.visit_pick(&mut ctx);
         assert_eq!(
             result,
-            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
+            Err("Invalid PICK instruction: empty args".to_string())
         );
     }
 
     #[test]
-    fn test_interpret_mult() {
-        let v1: Value = 0x1;
-        let v2: Value = 0x2;
+    fn test_interpret_depth() {
         let mut ctx = InterpreterState::new(0);
-        ctx.push_value(v1);
-        ctx.push_value(v2);
-
-        let mut mult_instr = Instruction {
-            opcode: Some(OpCodes::MULT),
+        ctx.push_value(0x7);
+        ctx.push_value(0x7);
+        let mut depth_instr = Instruction {
+            opcode: Some(OpCodes::DPTH),
             args: None,
         };
 
-        let result = mult_instr.visit_mult(&mut ctx);
+        let result = depth_instr.visit_depth(&mut ctx);
         assert!(result.is_ok());
-        let on_stack = ctx.pop_value();
-        assert!(on_stack.is_ok());
-        assert_eq!(on_stack.ok().unwrap(), v1 * v2);
         assert_eq!(ctx.ip, 0x1);
+        assert_eq!(stack_of(&mut ctx), vec![0x7, 0x7, 0x2]);
     }
 
     #[test]
-    fn test_interpret_bad_mult_excessive_args() {
+    fn test_interpret_bad_depth_excessive_args() {
         let mut ctx = InterpreterState::new(0);
-        let mut mult_instr = Instruction {
-            opcode: Some(OpCodes::MULT),
-            args: Some(vec![0x1, 0x1]),
+        let mut depth_instr = Instruction {
+            opcode: Some(OpCodes::DPTH),
+            args: Some(vec![0x1]),
         };
 
-        let result = mult_instr.visit_mult(&mut ctx);
+        let result = depth_instr.visit_depth(&mut ctx);
         assert_eq!(
             result,
-            Err("Invalid MULT instruction: unexpected args".to_string())
+            Err("Invalid DPTH instruction: unexpected args".to_string())
         );
     }
 
+    fn run_binary_op(opcode: OpCodes, v2: Value, v1: Value) -> Result<Value, String> {
+        let mut ctx = InterpreterState::new(0);
+        ctx.push_value(v2);
+        ctx.push_value(v1);
+        let mut instr = Instruction {
+            opcode: Some(opcode),
+            args: None,
+        };
+        match opcode {
+            OpCodes::AND => instr.visit_and(&mut ctx),
+            OpCodes::OR => instr.visit_or(&mut ctx),
+            OpCodes::XOR => instr.visit_xor(&mut ctx),
+            OpCodes::SHL => instr.visit_shl(&mut ctx),
+            OpCodes::SHR => instr.visit_shr(&mut ctx),
+            other => panic!("{:?} is not a binary operation", other),
+        }?;
+        assert_eq!(ctx.ip, 0x1);
+        ctx.pop_value()
+    }
+
     #[test]
-    fn test_interpret_bad_mult_empty_stack() {
+    fn test_interpret_bitwise() {
+        assert_eq!(run_binary_op(OpCodes::AND, 0b1100, 0b1010), Ok(0b1000));
+        assert_eq!(run_binary_op(OpCodes::OR, 0b1100, 0b1010), Ok(0b1110));
+        assert_eq!(run_binary_op(OpCodes::XOR, 0b1100, 0b1010), Ok(0b0110));
+    }
+
+    #[test]
+    fn test_interpret_shifts() {
+        // The shift amount is on top of the stack, the shifted value below it.
+        assert_eq!(run_binary_op(OpCodes::SHL, 0x1, 4), Ok(0x10));
+        assert_eq!(run_binary_op(OpCodes::SHR, 0x80, 4), Ok(0x8));
+        assert_eq!(run_binary_op(OpCodes::SHL, 0x8001, 1), Ok(0x2));
+        assert_eq!(run_binary_op(OpCodes::SHL, 0xFFFF, 15), Ok(0x8000));
+        assert_eq!(run_binary_op(OpCodes::SHL, 0xFFFF, 16), Ok(0));
+        assert_eq!(run_binary_op(OpCodes::SHR, 0xFFFF, 16), Ok(0));
+        assert_eq!(run_binary_op(OpCodes::SHR, 0xFFFF, 1000), Ok(0));
+    }
+
+    #[test]
+    fn test_interpret_bad_and_empty_stack() {
         let mut ctx = InterpreterState::new(0);
-        let mut mult_instr = Instruction {
-            opcode: Some(OpCodes::MULT),
+        ctx.push_value(0x1);
+        let mut and_instr = Instruction {
+            opcode: Some(OpCodes::AND),
             args: None,
         };
 
-        let result = mult_instr.visit_mult(&mut ctx);
+        let result = and_instr.visit_and(&mut ctx);
         assert_eq!(
             result,
             Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
@@ -553,129 +2249,219 @@ mod tests {
     }
 
     #[test]
-    fn test_interpret_rtn() {
+    fn test_interpret_bad_shl_excessive_args() {
         let mut ctx = InterpreterState::new(0);
+        let mut shl_instr = Instruction {
+            opcode: Some(OpCodes::SHL),
+            args: Some(vec![0x1]),
+        };
 
-        let mut rtn_instr = Instruction {
-            opcode: Some(OpCodes::RTN),
+        let result = shl_instr.visit_shl(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid SHL instruction: unexpected args".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_not() {
+        let mut ctx = InterpreterState::new(0);
+        ctx.push_value(0x00FF);
+        let mut not_instr = Instruction {
+            opcode: Some(OpCodes::NOT),
             args: None,
         };
 
-        let result = rtn_instr.visit_rtn(&mut ctx);
+        let result = not_instr.visit_not(&mut ctx);
         assert!(result.is_ok());
+        assert_eq!(ctx.pop_value(), Ok(0xFF00));
+        assert_eq!(ctx.ip, 0x1);
     }
 
     #[test]
-    fn test_interpret_bad_rtn_excessive_args() {
+    fn test_interpret_bad_not_empty_stack() {
         let mut ctx = InterpreterState::new(0);
-        let mut rtn_instr = Instruction {
-            opcode: Some(OpCodes::RTN),
-            args: Some(vec![0x1]),
+        let mut not_instr = Instruction {
+            opcode: Some(OpCodes::NOT),
+            args: None,
         };
 
-        let result = rtn_instr.visit_rtn(&mut ctx);
+        let result = not_instr.visit_not(&mut ctx);
         assert_eq!(
             result,
-            Err("Invalid RTN instruction: unexpected args".to_string())
+            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
         );
     }
 
-    #[test]
-    fn test_interpret_tegt() {
-        let v1: Value = 0x1;
-        let v2: Value = 0x2;
+    /// Runs a comparison or boolean operation on a stack holding `second`
+    /// below `first`, so `first` is the value popped first.
+    fn run_comparison(opcode: OpCodes, first: Value, second: Value) -> Value {
         let mut ctx = InterpreterState::new(0);
-        ctx.push_value(v1);
-        ctx.push_value(v2);
-
-        let mut tegt_instr = Instruction {
-            opcode: Some(OpCodes::TEGT),
+        ctx.push_value(second);
+        ctx.push_value(first);
+        let mut instr = Instruction {
+            opcode: Some(opcode),
             args: None,
         };
-
-        let result = tegt_instr.visit_test_gt(&mut ctx);
+        let result = match opcode {
+            OpCodes::TEGT => instr.visit_test_gt(&mut ctx),
+            OpCodes::TELT => instr.visit_test_lt(&mut ctx),
+            OpCodes::TEEQ => instr.visit_test_eq(&mut ctx),
+            OpCodes::TENE => instr.visit_test_ne(&mut ctx),
+            OpCodes::TEGE => instr.visit_test_ge(&mut ctx),
+            OpCodes::TELE => instr.visit_test_le(&mut ctx),
+            OpCodes::BAND => instr.visit_bool_and(&mut ctx),
+            OpCodes::BOR => instr.visit_bool_or(&mut ctx),
+            other => panic!("{:?} is not a comparison", other),
+        };
         assert!(result.is_ok());
-        let on_stack = ctx.pop_value();
-        assert!(on_stack.is_ok());
-        assert_eq!(on_stack.ok().unwrap(), 0x1);
         assert_eq!(ctx.ip, 0x1);
+        assert_eq!(ctx.stack.len(), 1);
+        ctx.pop_value().unwrap()
     }
 
     #[test]
-    fn test_interpret_bad_tegt_excessive_args() {
+    fn test_interpret_comparison_operand_order() {
+        // `LOAD_VAL 1 | LOAD_VAL 2 | TEST_GT` compares 2 (popped first) to 1.
+        assert_eq!(run_comparison(OpCodes::TEGT, 2, 1), 1);
+        assert_eq!(run_comparison(OpCodes::TEGT, 1, 2), 0);
+        assert_eq!(run_comparison(OpCodes::TELT, 1, 2), 1);
+        assert_eq!(run_comparison(OpCodes::TELT, 2, 1), 0);
+        assert_eq!(run_comparison(OpCodes::TEGE, 2, 1), 1);
+        assert_eq!(run_comparison(OpCodes::TEGE, 1, 2), 0);
+        assert_eq!(run_comparison(OpCodes::TELE, 1, 2), 1);
+        assert_eq!(run_comparison(OpCodes::TELE, 2, 1), 0);
+    }
+
+    #[test]
+    fn test_interpret_comparison_equal_operands() {
+        assert_eq!(run_comparison(OpCodes::TEEQ, 3, 3), 1);
+        assert_eq!(run_comparison(OpCodes::TENE, 3, 3), 0);
+        assert_eq!(run_comparison(OpCodes::TENE, 3, 4), 1);
+        assert_eq!(run_comparison(OpCodes::TEGE, 3, 3), 1);
+        assert_eq!(run_comparison(OpCodes::TELE, 3, 3), 1);
+        assert_eq!(run_comparison(OpCodes::TEGT, 3, 3), 0);
+        assert_eq!(run_comparison(OpCodes::TELT, 3, 3), 0);
+    }
+
+    #[test]
+    fn test_interpret_bool_ops() {
+        assert_eq!(run_comparison(OpCodes::BAND, 1, 1), 1);
+        assert_eq!(run_comparison(OpCodes::BAND, 5, 7), 1);
+        assert_eq!(run_comparison(OpCodes::BAND, 0, 1), 0);
+        assert_eq!(run_comparison(OpCodes::BAND, 1, 0), 0);
+        assert_eq!(run_comparison(OpCodes::BOR, 0, 0), 0);
+        assert_eq!(run_comparison(OpCodes::BOR, 0, 2), 1);
+        assert_eq!(run_comparison(OpCodes::BOR, 2, 0), 1);
+    }
+
+    #[test]
+    fn test_interpret_bool_not() {
+        for (value, expected) in [(0, 1), (1, 0), (0xFF, 0)] {
+            let mut ctx = InterpreterState::new(0);
+            ctx.push_value(value);
+            let mut not_instr = Instruction {
+                opcode: Some(OpCodes::BNOT),
+                args: None,
+            };
+
+            let result = not_instr.visit_bool_not(&mut ctx);
+            assert!(result.is_ok());
+            assert_eq!(ctx.pop_value(), Ok(expected));
+            assert_eq!(ctx.ip, 0x1);
+        }
+    }
+
+    #[test]
+    fn test_interpret_bad_test_ne_empty_stack() {
         let mut ctx = InterpreterState::new(0);
-        let mut tegt_instr = Instruction {
-            opcode: Some(OpCodes::TEGT),
-            args: Some(vec![0x1, 0x1]),
+        ctx.push_value(0x1);
+        let mut ne_instr = Instruction {
+            opcode: Some(OpCodes::TENE),
+            args: None,
         };
 
-        let result = tegt_instr.visit_test_gt(&mut ctx);
+        let result = ne_instr.visit_test_ne(&mut ctx);
         assert_eq!(
             result,
-            Err("Invalid TEGT instruction: unexpected args".to_string())
+            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
         );
     }
 
     #[test]
-    fn test_interpret_bad_tegt_empty_stack() {
+    fn test_interpret_bad_bool_and_excessive_args() {
         let mut ctx = InterpreterState::new(0);
-        let mut tegt_instr = Instruction {
-            opcode: Some(OpCodes::TEGT),
-            args: None,
+        let mut and_instr = Instruction {
+            opcode: Some(OpCodes::BAND),
+            args: Some(vec![0x1]),
         };
 
-        let result = tegt_instr.visit_test_gt(&mut ctx);
+        let result = and_instr.visit_bool_and(&mut ctx);
         assert_eq!(
             result,
-            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
+            Err("Invalid BAND instruction: unexpected args".to_string())
         );
     }
 
     #[test]
-    fn test_interpret_telt() {
-        let v1: Value = 0x1;
-        let v2: Value = 0x2;
+    fn test_interpret_jump() {
         let mut ctx = InterpreterState::new(0);
-        ctx.push_value(v1);
-        ctx.push_value(v2);
-
-        let mut telt_instr = Instruction {
-            opcode: Some(OpCodes::TELT),
-            args: None,
+        ctx.push_value(0x0);
+        let mut jump_instr = Instruction {
+            opcode: Some(OpCodes::JUMP),
+            args: Some(vec![0x5]),
         };
 
-        let result = telt_instr.visit_test_lt(&mut ctx);
+        let result = jump_instr.visit_jump(&mut ctx);
         assert!(result.is_ok());
-        let on_stack = ctx.pop_value();
-        assert!(on_stack.is_ok());
-        assert_eq!(on_stack.ok().unwrap(), 0x0);
-        assert_eq!(ctx.ip, 0x1);
+        assert_eq!(ctx.ip, 0x5);
+        // JUMP leaves the stack untouched.
+        assert_eq!(ctx.pop_value(), Ok(0x0));
     }
 
     #[test]
-    fn test_interpret_bad_telt_excessive_args() {
+    fn test_interpret_bad_jump_empty_args() {
         let mut ctx = InterpreterState::new(0);
-        let mut telt_instr = Instruction {
-            opcode: Some(OpCodes::TELT),
-            args: Some(vec![0x1, 0x1]),
+        let mut jump_instr = Instruction {
+            opcode: Some(OpCodes::JUMP),
+            args: None,
         };
 
-        let result = telt_instr.visit_test_lt(&mut ctx);
+        let result = jump_instr.visit_jump(&mut ctx);
         assert_eq!(
             result,
-            Err("Invalid TELT instruction: unexpected args".to_string())
+            Err("Invalid JUMP instruction: empty args".to_string())
         );
     }
 
     #[test]
-    fn test_interpret_bad_telt_empty_stack() {
+    fn test_interpret_goto_if_zero() {
         let mut ctx = InterpreterState::new(0);
-        let mut telt_instr = Instruction {
-            opcode: Some(OpCodes::TELT),
-            args: None,
+        ctx.push_value(0x1);
+        ctx.push_value(0x0);
+        let mut gotz_instr = Instruction {
+            opcode: Some(OpCodes::GOTZ),
+            args: Some(vec![0x5]),
         };
 
-        let result = telt_instr.visit_test_lt(&mut ctx);
+        let result = gotz_instr.visit_goto_if_zero(&mut ctx);
+        assert!(result.is_ok());
+        assert_eq!(ctx.ip, 0x5);
+
+        let result = gotz_instr.visit_goto_if_zero(&mut ctx);
+        assert!(result.is_ok());
+        assert_eq!(ctx.ip, 0x6);
+    }
+
+    #[test]
+    fn test_interpret_bad_goto_if_zero_empty_stack() {
+        let mut ctx = InterpreterState::new(0);
+        let mut gotz_instr = Instruction {
+            opcode: Some(OpCodes::GOTZ),
+            args: Some(vec![0x5]),
+        };
+
+        let result = gotz_instr.visit_goto_if_zero(&mut ctx);
         assert_eq!(
             result,
             Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
@@ -683,229 +2469,343 @@ mod tests {
     }
 
     #[test]
-    fn test_interpret_teeq() {
-        let v1: Value = 0x1;
-        let v2: Value = 0x1;
+    fn test_interpret_bad_goto_if_zero_excessive_args() {
         let mut ctx = InterpreterState::new(0);
-        ctx.push_value(v1);
-        ctx.push_value(v2);
-
-        let mut teeq_instr = Instruction {
-            opcode: Some(OpCodes::TEEQ),
-            args: None,
+        let mut gotz_instr = Instruction {
+            opcode: Some(OpCodes::GOTZ),
+            args: Some(vec![0x1, 0x2]),
         };
 
-        let result = teeq_instr.visit_test_eq(&mut ctx);
-        assert!(result.is_ok());
-        let on_stack = ctx.pop_value();
-        assert!(on_stack.is_ok());
-        assert_eq!(on_stack.ok().unwrap(), 0x1);
-        assert_eq!(ctx.ip, 0x1);
+        let result = gotz_instr.visit_goto_if_zero(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Invalid GOTZ instruction: expected 1 argument, got 2".to_string())
+        );
     }
 
     #[test]
-    fn test_interpret_bad_teeq_excessive_args() {
+    fn test_interpret_bad_goto_if_zero_strict_non_boolean() {
         let mut ctx = InterpreterState::new(0);
-        let mut teeq_instr = Instruction {
-            opcode: Some(OpCodes::TEEQ),
-            args: Some(vec![0x1, 0x1]),
+        ctx.strict = true;
+        ctx.push_value(0x2);
+        let mut gotz_instr = Instruction {
+            opcode: Some(OpCodes::GOTZ),
+            args: Some(vec![0x5]),
         };
 
-        let result = teeq_instr.visit_test_eq(&mut ctx);
+        let result = gotz_instr.visit_goto_if_zero(&mut ctx);
         assert_eq!(
             result,
-            Err("Invalid TEEQ instruction: unexpected args".to_string())
+            Err("Runtime error: unable to process current instruction, ip = 0x00: condition 2 is not a boolean".to_string())
         );
     }
 
     #[test]
-    fn test_interpret_bad_teeq_empty_stack() {
+    fn test_interpret_switch() {
+        let mut switch_instr = Instruction {
+            opcode: Some(OpCodes::SWCH),
+            args: Some(vec![0x4, 0x6, 0x9]),
+        };
+        for (index, target) in [(0, 0x4), (1, 0x6), (2, 0x9), (300, 0x9)] {
+            let mut ctx = InterpreterState::new(0);
+            ctx.push_value(index);
+            let result = switch_instr.visit_switch(&mut ctx);
+            assert!(result.is_ok());
+            assert_eq!(ctx.ip, target);
+        }
+    }
+
+    #[test]
+    fn test_interpret_bad_switch_empty_args() {
         let mut ctx = InterpreterState::new(0);
-        let mut teeq_instr = Instruction {
-            opcode: Some(OpCodes::TEEQ),
+        ctx.push_value(0x0);
+        let mut switch_instr = Instruction {
+            opcode: Some(OpCodes::SWCH),
             args: None,
         };
 
-        let result = teeq_instr.visit_test_eq(&mut ctx);
+        let result = switch_instr.visit_switch(&mut ctx);
         assert_eq!(
             result,
-            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
+            Err("Invalid SWCH instruction: empty args".to_string())
         );
     }
 
     #[test]
-    fn test_interpret_goto_false() {
-        let desired_ip = 0x5;
+    fn test_interpret_try_catch_fault() {
         let mut ctx = InterpreterState::new(0);
-        ctx.push_value(0x0);
-
-        let mut goto_instr = Instruction {
-            opcode: Some(OpCodes::GOTO),
-            args: Some(vec![desired_ip]),
+        ctx.push_value(0x7);
+        let mut try_instr = Instruction {
+            opcode: Some(OpCodes::TRY),
+            args: Some(vec![0x9]),
         };
+        assert!(try_instr.visit_try(&mut ctx).is_ok());
+        ctx.push_value(0x8);
+        ctx.push_value(0x3);
 
-        let result = goto_instr.visit_goto(&mut ctx);
-        assert!(result.is_ok());
-        assert_eq!(ctx.ip, 0x1);
+        let mut throw_instr = Instruction {
+            opcode: Some(OpCodes::THRW),
+            args: None,
+        };
+        assert!(throw_instr.visit_throw(&mut ctx).is_err());
+        assert!(ctx.catch_fault());
+        assert_eq!(ctx.ip, 0x9);
+        assert_eq!(stack_of(&mut ctx), vec![0x7, 0x3]);
     }
 
     #[test]
-    fn test_interpret_goto_true() {
-        let desired_ip = 0x5;
+    fn test_interpret_uncaught_fault() {
         let mut ctx = InterpreterState::new(0);
-        ctx.push_value(0x1);
-
-        let mut goto_instr = Instruction {
-            opcode: Some(OpCodes::GOTO),
-            args: Some(vec![desired_ip]),
+        let mut try_instr = Instruction {
+            opcode: Some(OpCodes::TRY),
+            args: Some(vec![0x9]),
+        };
+        let mut end_try_instr = Instruction {
+            opcode: Some(OpCodes::ETRY),
+            args: None,
         };
+        assert!(try_instr.visit_try(&mut ctx).is_ok());
+        assert!(end_try_instr.visit_end_try(&mut ctx).is_ok());
+        assert_eq!(
+            end_try_instr.visit_end_try(&mut ctx),
+            Err("Runtime error: unable to process current instruction, ip = 0x02: END_TRY without TRY".to_string())
+        );
+        assert!(!ctx.catch_fault());
 
-        let result = goto_instr.visit_goto(&mut ctx);
-        assert!(result.is_ok());
-        assert_eq!(ctx.ip, desired_ip as usize);
+        assert!(ctx.pop_value().is_err());
+        assert!(!ctx.catch_fault());
     }
 
     #[test]
-    fn test_interpret_bad_goto_excessive_args() {
+    fn test_interpret_throw() {
         let mut ctx = InterpreterState::new(0);
-        let mut goto_instr = Instruction {
-            opcode: Some(OpCodes::GOTO),
-            args: Some(vec![0x1, 0x1]),
+        ctx.push_value(0x2A);
+        let mut throw_instr = Instruction {
+            opcode: Some(OpCodes::THRW),
+            args: None,
         };
 
-        let result = goto_instr.visit_goto(&mut ctx);
+        let result = throw_instr.visit_throw(&mut ctx);
         assert_eq!(
             result,
-            Err("Invalid GOTO instruction: expected 1 argument, got 2".to_string())
+            Err("Runtime error: unable to process current instruction, ip = 0x00: uncaught exception 42".to_string())
         );
+        assert_eq!(ctx.fault, Some(0x2A));
     }
 
     #[test]
-    fn test_interpret_bad_goto_empty_args() {
+    fn test_interpret_checked_overflow() {
         let mut ctx = InterpreterState::new(0);
-        let mut goto_instr = Instruction {
-            opcode: Some(OpCodes::GOTO),
+        ctx.checked = true;
+        ctx.push_value(0x100);
+        ctx.push_value(0x100);
+        let mut mult_instr = Instruction {
+            opcode: Some(OpCodes::MULT),
             args: None,
         };
 
-        let result = goto_instr.visit_goto(&mut ctx);
+        let result = mult_instr.visit_mult(&mut ctx);
         assert_eq!(
             result,
-            Err("Invalid GOTO instruction: empty args".to_string())
+            Err("Runtime error: unable to process current instruction, ip = 0x00: arithmetic overflow in 256 * 256".to_string())
         );
+        assert_eq!(ctx.fault, Some(fault::OVERFLOW));
     }
 
     #[test]
-    fn test_interpret_bad_goto_empty_stack() {
+    fn test_interpret_assert() {
         let mut ctx = InterpreterState::new(0);
-        let mut goto_instr = Instruction {
-            opcode: Some(OpCodes::GOTO),
-            args: Some(vec![0x1]),
+        ctx.strings = vec!["x is set".to_string(), "lib.mor:7".to_string()];
+        ctx.push_value(0x4);
+        ctx.push_value(0x1);
+        let mut assert_instr = Instruction {
+            opcode: Some(OpCodes::ASRT),
+            args: Some(vec![0x0, 0x1]),
         };
 
-        let result = goto_instr.visit_goto(&mut ctx);
+        let result = assert_instr.visit_assert(&mut ctx);
+        assert!(result.is_ok());
+        assert_eq!(ctx.ip, 0x1);
+
+        ctx.push_value(0x0);
+        let result = assert_instr.visit_assert(&mut ctx);
         assert_eq!(
             result,
-            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
+            Err("Runtime error: unable to process current instruction, ip = 0x01: assertion failed at lib.mor:7: x is set, stack: [4]".to_string())
         );
     }
 
     #[test]
-    fn test_interpret_dup() {
-        let v1: Value = 0x1;
+    fn test_interpret_call_host() {
         let mut ctx = InterpreterState::new(0);
-        ctx.push_value(v1);
-
-        let mut dup_instr = Instruction {
-            opcode: Some(OpCodes::DUP),
-            args: None,
+        ctx.strings = vec!["sub".to_string()];
+        ctx.hosts.insert(
+            "sub".to_string(),
+            HostFunction {
+                arity: 2,
+                function: Box::new(|args: &[Value]| match args[0].checked_sub(args[1]) {
+                    Some(v) => Ok(vec![v, 0x1]),
+                    None => Err("negative result".to_string()),
+                }),
+            },
+        );
+        ctx.push_value(0x9);
+        ctx.push_value(0x7);
+        ctx.push_value(0x2);
+        let mut host_instr = Instruction {
+            opcode: Some(OpCodes::HOST),
+            args: Some(vec![0x0]),
         };
 
-        let result = dup_instr.visit_dup(&mut ctx);
+        let result = host_instr.visit_call_host(&mut ctx);
         assert!(result.is_ok());
-        let on_stack_1 = ctx.pop_value();
-        let on_stack_2 = ctx.pop_value();
-        assert!(on_stack_1.is_ok());
-        assert!(on_stack_2.is_ok());
-        assert_eq!(on_stack_1.ok().unwrap(), 0x1);
-        assert_eq!(on_stack_2.ok().unwrap(), 0x1);
+        assert_eq!(ctx.stack.stack, vec![0x9, 0x5, 0x1]);
         assert_eq!(ctx.ip, 0x1);
+
+        ctx.push_value(0x6);
+        let result = host_instr.visit_call_host(&mut ctx);
+        assert_eq!(
+            result,
+            Err("Runtime error: unable to process current instruction, ip = 0x01: host function sub failed: negative result".to_string())
+        );
+        assert_eq!(ctx.fault, Some(fault::HOST_ERROR));
     }
 
     #[test]
-    fn test_interpret_bad_dup_excessive_args() {
+    fn test_interpret_unknown_host() {
         let mut ctx = InterpreterState::new(0);
-        let mut dup_instr = Instruction {
-            opcode: Some(OpCodes::DUP),
-            args: Some(vec![0x1, 0x1]),
+        ctx.strings = vec!["price".to_string()];
+        let mut host_instr = Instruction {
+            opcode: Some(OpCodes::HOST),
+            args: Some(vec![0x0]),
         };
 
-        let result = dup_instr.visit_dup(&mut ctx);
+        let result = host_instr.visit_call_host(&mut ctx);
         assert_eq!(
             result,
-            Err("Invalid DUP instruction: unexpected args".to_string())
+            Err("Runtime error: unable to process current instruction, ip = 0x00: unknown host function price".to_string())
         );
     }
 
-    #[test]
-    fn test_interpret_bad_dup_empty_stack() {
+    fn run_builtin(opcode: OpCodes, values: &[Value]) -> Result<Vec<Value>, String> {
         let mut ctx = InterpreterState::new(0);
-        let mut dup_instr = Instruction {
-            opcode: Some(OpCodes::DUP),
+        values.iter().for_each(|v| ctx.push_value(*v));
+        let mut instr = Instruction {
+            opcode: Some(opcode),
             args: None,
         };
+        match opcode {
+            OpCodes::MIN => instr.visit_min(&mut ctx),
+            OpCodes::MAX => instr.visit_max(&mut ctx),
+            OpCodes::ABS => instr.visit_abs(&mut ctx),
+            OpCodes::POW => instr.visit_pow(&mut ctx),
+            OpCodes::GCD => instr.visit_gcd(&mut ctx),
+            OpCodes::SQRT => instr.visit_sqrt_int(&mut ctx),
+            OpCodes::CLMP => instr.visit_clamp(&mut ctx),
+            _ => unreachable!(),
+        }?;
+        assert_eq!(ctx.ip, 0x1);
+        Ok(ctx.stack.stack)
+    }
 
-        let result = dup_instr.visit_dup(&mut ctx);
+    #[test]
+    fn test_interpret_min_max() {
         assert_eq!(
-            result,
+            run_builtin(OpCodes::MIN, &[0x1, 0x7, 0x3]),
+            Ok(vec![0x1, 0x3])
+        );
+        assert_eq!(
+            run_builtin(OpCodes::MAX, &[0x1, 0x7, 0x3]),
+            Ok(vec![0x1, 0x7])
+        );
+        assert_eq!(
+            run_builtin(OpCodes::MAX, &[0x7]),
             Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
         );
     }
 
     #[test]
-    fn test_interpret_pop() {
+    fn test_interpret_abs() {
+        assert_eq!(run_builtin(OpCodes::ABS, &[0x5]), Ok(vec![0x5]));
+        assert_eq!(run_builtin(OpCodes::ABS, &[0xFFFB]), Ok(vec![0x5]));
+        assert_eq!(run_builtin(OpCodes::ABS, &[0x8000]), Ok(vec![0x8000]));
+    }
+
+    #[test]
+    fn test_interpret_pow() {
+        assert_eq!(run_builtin(OpCodes::POW, &[0x3, 0x4]), Ok(vec![0x51]));
+        assert_eq!(run_builtin(OpCodes::POW, &[0x0, 0x0]), Ok(vec![0x1]));
+        assert_eq!(run_builtin(OpCodes::POW, &[0x3, 0xB]), Ok(vec![0xB3FB]));
+        assert_eq!(run_builtin(OpCodes::POW, &[0x2, 0x14]), Ok(vec![0x0]));
+
         let mut ctx = InterpreterState::new(0);
+        ctx.checked = true;
         ctx.push_value(0x2);
-
-        let mut pop_instr = Instruction {
-            opcode: Some(OpCodes::POP),
+        ctx.push_value(0x10);
+        let mut pow_instr = Instruction {
+            opcode: Some(OpCodes::POW),
             args: None,
         };
+        assert_eq!(
+            pow_instr.visit_pow(&mut ctx),
+            Err("Runtime error: unable to process current instruction, ip = 0x00: arithmetic overflow in 2 ** 16".to_string())
+        );
+        assert_eq!(ctx.fault, Some(fault::OVERFLOW));
+    }
 
-        let result = pop_instr.visit_pop(&mut ctx);
-        assert!(result.is_ok());
-        let on_stack = ctx.pop_value();
-        assert_eq!(on_stack, Err("Runtime error: unable to process current instruction, ip = 0x01: no value on stack".to_string()));
-        assert_eq!(ctx.ip, 0x1);
+    #[test]
+    fn test_interpret_gcd() {
+        assert_eq!(run_builtin(OpCodes::GCD, &[0xC, 0x12]), Ok(vec![0x6]));
+        assert_eq!(run_builtin(OpCodes::GCD, &[0x0, 0x9]), Ok(vec![0x9]));
+        assert_eq!(run_builtin(OpCodes::GCD, &[0x0, 0x0]), Ok(vec![0x0]));
     }
 
     #[test]
-    fn test_interpret_bad_pop_excessive_args() {
-        let mut ctx = InterpreterState::new(0);
-        let mut pop_instr = Instruction {
-            opcode: Some(OpCodes::POP),
-            args: Some(vec![0x1, 0x1]),
-        };
+    fn test_interpret_sqrt_int() {
+        assert_eq!(run_builtin(OpCodes::SQRT, &[0x50]), Ok(vec![0x8]));
+        assert_eq!(run_builtin(OpCodes::SQRT, &[0x51]), Ok(vec![0x9]));
+        assert_eq!(run_builtin(OpCodes::SQRT, &[0xFFFF]), Ok(vec![0xFF]));
+    }
 
-        let result = pop_instr.visit_pop(&mut ctx);
+    #[test]
+    fn test_interpret_clamp() {
+        assert_eq!(run_builtin(OpCodes::CLMP, &[0x1, 0x3, 0x5]), Ok(vec![0x3]));
+        assert_eq!(run_builtin(OpCodes::CLMP, &[0x4, 0x3, 0x5]), Ok(vec![0x4]));
+        assert_eq!(run_builtin(OpCodes::CLMP, &[0x9, 0x3, 0x5]), Ok(vec![0x5]));
         assert_eq!(
-            result,
-            Err("Invalid POP instruction: unexpected args".to_string())
+            run_builtin(OpCodes::CLMP, &[0x4, 0x5, 0x3]),
+            Err("Runtime error: unable to process current instruction, ip = 0x00: CLAMP lower bound 5 is greater than upper bound 3".to_string())
         );
     }
 
     #[test]
-    fn test_interpret_bad_pop_empty_stack() {
+    fn test_interpret_rand() {
         let mut ctx = InterpreterState::new(0);
-        let mut pop_instr = Instruction {
-            opcode: Some(OpCodes::POP),
+        let mut rand_instr = Instruction {
+            opcode: Some(OpCodes::RAND),
             args: None,
         };
+        let mut draws = Vec::new();
+        for _ in 0..8 {
+            ctx.push_value(0xA);
+            assert!(rand_instr.visit_rand(&mut ctx).is_ok());
+            draws.push(ctx.pop_value().unwrap());
+        }
+        assert!(draws.iter().all(|v| *v < 0xA));
+        assert_eq!(ctx.ip, 0x8);
 
-        let result = pop_instr.visit_pop(&mut ctx);
+        let mut ctx = InterpreterState::new(0);
+        for expected in draws {
+            ctx.push_value(0xA);
+            assert!(rand_instr.visit_rand(&mut ctx).is_ok());
+            assert_eq!(ctx.pop_value(), Ok(expected));
+        }
+
+        ctx.push_value(0x0);
         assert_eq!(
-            result,
-            Err("Runtime error: unable to process current instruction, ip = 0x00: no value on stack".to_string())
+            rand_instr.visit_rand(&mut ctx),
+            Err("Runtime error: unable to process current instruction, ip = 0x08: RAND bound must be positive".to_string())
         );
+        assert_eq!(ctx.fault, Some(fault::INVALID_ARGUMENT));
     }
 }
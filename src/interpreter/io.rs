@@ -0,0 +1,50 @@
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+
+/// Input source and output sink used by the `READ_INPUT`, `PRINT` and
+/// `PRINT_STR` instructions.
+pub struct Io<'a> {
+    input: Box<dyn BufRead + 'a>,
+    output: Box<dyn Write + 'a>,
+}
+
+impl<'a> Io<'a> {
+    pub fn new<R: BufRead + 'a, W: Write + 'a>(input: R, output: W) -> Self {
+        Io {
+            input: Box::new(input),
+            output: Box::new(output),
+        }
+    }
+
+    /// Reads one line from the input source. Returns `Ok(None)` at the end of input.
+    pub fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        match self.input.read_line(&mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some(line)),
+        }
+    }
+
+    pub fn write_line(&mut self, s: &str) -> io::Result<()> {
+        writeln!(self.output, "{}", s)?;
+        self.output.flush()
+    }
+}
+
+impl Io<'static> {
+    pub fn stdio() -> Self {
+        Io::new(BufReader::new(io::stdin()), io::stdout())
+    }
+}
+
+impl Default for Io<'static> {
+    fn default() -> Self {
+        Io::stdio()
+    }
+}
+
+impl fmt::Debug for Io<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Io").finish_non_exhaustive()
+    }
+}
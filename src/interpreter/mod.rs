@@ -1,11 +1,36 @@
+mod fault;
+mod host;
+#[allow(clippy::module_inception)]
 mod interpreter;
+mod io;
+mod random;
+mod report;
+mod snapshot;
 mod stack;
+mod vm;
 
 use crate::bytecode::ByteCode;
 use crate::config::Value;
-use interpreter::Interpreter;
+pub use interpreter::Interpreter;
+pub use io::Io;
+pub use report::{ExecutionReport, Termination};
+pub use vm::Vm;
 
 pub fn interpret(program: ByteCode) -> Result<Option<Value>, String> {
     let mut interpreter = Interpreter::new(program);
     interpreter.interpret()
 }
+
+pub fn interpret_with_io(program: ByteCode, io: Io) -> Result<Option<Value>, String> {
+    let mut interpreter = Interpreter::with_io(program, io);
+    interpreter.interpret()
+}
+
+pub fn interpret_with_args(
+    program: ByteCode,
+    args: &[(String, Value)],
+) -> Result<Option<Value>, String> {
+    let mut interpreter = Interpreter::new(program);
+    interpreter.set_args(args)?;
+    interpreter.interpret()
+}
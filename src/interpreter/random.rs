@@ -0,0 +1,55 @@
+use crate::config::Value;
+
+/// Seed used by RAND unless the interpreter is given another one.
+pub const DEFAULT_SEED: u64 = 0x5EED;
+
+/// SplitMix64 generator behind RAND: a seed always gives the same sequence on
+/// every platform.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Random { state: seed }
+    }
+    /// Returns the state, from which `new` resumes the same sequence.
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    /// Returns a value in `0..bound`, `bound` being positive.
+    pub fn below(&mut self, bound: Value) -> Value {
+        (self.next_u64() % u64::from(bound)) as Value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = Random::new(42);
+        let mut b = Random::new(42);
+        let sequence = (0..8).map(|_| a.below(100)).collect::<Vec<_>>();
+        assert_eq!(sequence, (0..8).map(|_| b.below(100)).collect::<Vec<_>>());
+        assert!(sequence.iter().all(|v| *v < 100));
+        let mut c = Random::new(43);
+        assert_ne!(sequence, (0..8).map(|_| c.below(100)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn splitmix64_reference() {
+        let mut random = Random::new(1234567);
+        assert_eq!(random.next_u64(), 6457827717110365317);
+        assert_eq!(random.next_u64(), 3203168211198807973);
+    }
+}
@@ -0,0 +1,30 @@
+use crate::config::Value;
+use std::collections::BTreeMap;
+
+/// How a program run ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Termination {
+    /// `RETURN_VALUE` was executed.
+    Returned,
+    /// The instruction pointer moved past the last instruction.
+    FellOffEnd,
+    /// The run stopped with an uncaught error.
+    Error(String),
+}
+
+/// Everything known about a program run once it has ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionReport {
+    /// Value popped by `RETURN_VALUE`, if the program returned.
+    pub result: Option<Value>,
+    pub termination: Termination,
+    /// Values left on the stack, the top last.
+    pub stack: Vec<Value>,
+    /// Final values of the variables visible at the end of the program.
+    /// Variables declared in `.scope` blocks are not included.
+    pub vars: BTreeMap<String, Value>,
+    /// Number of instructions executed, including the failed one if any.
+    pub steps: usize,
+    /// Largest number of values the stack held at once.
+    pub max_stack_depth: usize,
+}
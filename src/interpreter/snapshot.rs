@@ -0,0 +1,243 @@
+//! Binary snapshot of a running program, written by `Vm::snapshot`.
+//!
+//! All integers are little-endian. The layout is:
+//!
+//! ```text
+//! magic "MORS", version: u8
+//! bytecode hash: u64
+//! ip: u16, steps: u64, max stack depth: u32
+//! stack: u32 count + u16 values
+//! vars: u16 count + u16 values
+//! handlers: u16 count, then for each TRY block: u16 target, u32 depth
+//! random generator state: u64
+//! result: u8 flag, then u16 value if the flag is 1
+//! termination: u8, 0 running, 1 returned, 2 fell off the end,
+//!     3 error followed by u32 length + UTF-8 message
+//! ```
+//!
+//! A state whose sizes do not fit in these fields cannot be saved.
+//!
+//! The hash is the FNV-1a hash of the binary bytecode, so a snapshot is only
+//! restored into the program it was taken from.
+
+use super::report::Termination;
+use crate::bytecode::ByteCode;
+use crate::config::Value;
+use crate::reader::Reader;
+
+const MAGIC: &[u8; 4] = b"MORS";
+const VERSION: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Snapshot {
+    pub bytecode_hash: u64,
+    pub ip: usize,
+    pub steps: usize,
+    pub max_stack_depth: usize,
+    pub stack: Vec<Value>,
+    pub vars: Vec<Value>,
+    /// Target and stack depth of the TRY blocks being executed.
+    pub handlers: Vec<(Value, usize)>,
+    pub random: u64,
+    pub result: Option<Value>,
+    pub termination: Option<Termination>,
+}
+
+/// Hashes the program with FNV-1a, which unlike the standard library hashers
+/// gives the same value in every build. Fails for programs that cannot be
+/// encoded.
+pub(super) fn hash_bytecode(bytecode: &ByteCode) -> Result<u64, String> {
+    let hash = bytecode
+        .to_bytes()?
+        .iter()
+        .fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01B3)
+        });
+    Ok(hash)
+}
+
+impl Snapshot {
+    pub fn encode(&self) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.extend_from_slice(&self.bytecode_hash.to_le_bytes());
+        out.extend_from_slice(&fit::<u16>(self.ip, "instruction pointer")?.to_le_bytes());
+        out.extend_from_slice(&fit::<u64>(self.steps, "step count")?.to_le_bytes());
+        out.extend_from_slice(&fit::<u32>(self.max_stack_depth, "stack depth")?.to_le_bytes());
+        out.extend_from_slice(&fit::<u32>(self.stack.len(), "stack depth")?.to_le_bytes());
+        self.stack
+            .iter()
+            .for_each(|v| out.extend_from_slice(&v.to_le_bytes()));
+        out.extend_from_slice(&fit::<u16>(self.vars.len(), "variable count")?.to_le_bytes());
+        self.vars
+            .iter()
+            .for_each(|v| out.extend_from_slice(&v.to_le_bytes()));
+        out.extend_from_slice(&fit::<u16>(self.handlers.len(), "TRY block count")?.to_le_bytes());
+        for (target, depth) in &self.handlers {
+            out.extend_from_slice(&target.to_le_bytes());
+            out.extend_from_slice(&fit::<u32>(*depth, "stack depth")?.to_le_bytes());
+        }
+        out.extend_from_slice(&self.random.to_le_bytes());
+        match self.result {
+            Some(v) => {
+                out.push(1);
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            None => out.push(0),
+        }
+        match &self.termination {
+            None => out.push(0),
+            Some(Termination::Returned) => out.push(1),
+            Some(Termination::FellOffEnd) => out.push(2),
+            Some(Termination::Error(e)) => {
+                out.push(3);
+                out.extend_from_slice(&fit::<u32>(e.len(), "error length")?.to_le_bytes());
+                out.extend_from_slice(e.as_bytes());
+            }
+        }
+        Ok(out)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader::new(bytes, "Invalid snapshot");
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err("Invalid snapshot: missing MORS header".to_string());
+        }
+        let version = reader.read_u8()?;
+        if version != VERSION {
+            return Err(format!(
+                "Invalid snapshot: unsupported format version {}",
+                version
+            ));
+        }
+        let bytecode_hash = reader.read_u64()?;
+        let ip = reader.read_u16()? as usize;
+        let steps = reader.read_u64()? as usize;
+        let max_stack_depth = reader.read_u32()? as usize;
+        let mut stack = Vec::new();
+        for _ in 0..reader.read_u32()? {
+            stack.push(reader.read_u16()?);
+        }
+        let mut vars = Vec::new();
+        for _ in 0..reader.read_u16()? {
+            vars.push(reader.read_u16()?);
+        }
+        let mut handlers = Vec::new();
+        for _ in 0..reader.read_u16()? {
+            let target = reader.read_u16()?;
+            handlers.push((target, reader.read_u32()? as usize));
+        }
+        let random = reader.read_u64()?;
+        let result = match reader.read_u8()? {
+            0 => None,
+            1 => Some(reader.read_u16()?),
+            other => return Err(format!("Invalid snapshot: unknown result flag {}", other)),
+        };
+        let termination = match reader.read_u8()? {
+            0 => None,
+            1 => Some(Termination::Returned),
+            2 => Some(Termination::FellOffEnd),
+            3 => {
+                let len = reader.read_u32()? as usize;
+                Some(Termination::Error(reader.read_utf8(len, "error")?))
+            }
+            other => return Err(format!("Invalid snapshot: unknown termination {}", other)),
+        };
+        reader.finish()?;
+        Ok(Snapshot {
+            bytecode_hash,
+            ip,
+            steps,
+            max_stack_depth,
+            stack,
+            vars,
+            handlers,
+            random,
+            result,
+            termination,
+        })
+    }
+}
+
+/// Converts a size for a field of the snapshot, refusing values that do not
+/// fit in it.
+fn fit<T: TryFrom<usize>>(value: usize, what: &str) -> Result<T, String> {
+    T::try_from(value)
+        .map_err(|_| format!("Unable to take snapshot: {} {} is too large", what, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            bytecode_hash: 0x0123_4567_89AB_CDEF,
+            ip: 7,
+            steps: 120,
+            max_stack_depth: 4,
+            stack: vec![1, 0xFFFF],
+            vars: vec![20, 0, 3],
+            handlers: vec![(9, 1)],
+            random: 42,
+            result: None,
+            termination: None,
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let snapshot = snapshot();
+        assert_eq!(Snapshot::decode(&snapshot.encode().unwrap()), Ok(snapshot));
+
+        let halted = Snapshot {
+            result: Some(5),
+            termination: Some(Termination::Error("no value on stack".to_string())),
+            ..self::snapshot()
+        };
+        assert_eq!(Snapshot::decode(&halted.encode().unwrap()), Ok(halted));
+    }
+
+    #[test]
+    fn encode_too_large() {
+        let snapshot = Snapshot {
+            vars: vec![0; 70000],
+            ..snapshot()
+        };
+        assert_eq!(
+            snapshot.encode(),
+            Err("Unable to take snapshot: variable count 70000 is too large".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_invalid() {
+        let bytes = snapshot().encode().unwrap();
+        assert_eq!(
+            Snapshot::decode(&bytes[..bytes.len() - 1]),
+            Err("Invalid snapshot: unexpected end of data".to_string())
+        );
+        assert_eq!(
+            Snapshot::decode(b"MORB\x02"),
+            Err("Invalid snapshot: missing MORS header".to_string())
+        );
+        let mut bytes = bytes;
+        bytes.push(0);
+        assert_eq!(
+            Snapshot::decode(&bytes),
+            Err("Invalid snapshot: trailing data".to_string())
+        );
+    }
+
+    #[test]
+    fn hash_depends_on_program() {
+        let transpile = |code: &str| ByteCode::transpile_reader(&mut code.as_bytes()).unwrap();
+        let program = transpile("LOAD_VAL 1 | RETURN_VALUE");
+        assert_eq!(hash_bytecode(&program), hash_bytecode(&program.clone()));
+        assert_ne!(
+            hash_bytecode(&program),
+            hash_bytecode(&transpile("LOAD_VAL 2 | RETURN_VALUE"))
+        );
+    }
+}
@@ -15,4 +15,17 @@ impl<T> Stack<T> {
     pub fn pop(&mut self) -> Option<T> {
         self.stack.pop()
     }
+
+    pub fn len(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.stack.truncate(len);
+    }
+
+    /// Returns the value `depth` positions below the top, `0` being the top.
+    pub fn peek(&self, depth: usize) -> Option<&T> {
+        self.stack.iter().rev().nth(depth)
+    }
 }
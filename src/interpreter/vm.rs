@@ -0,0 +1,222 @@
+use super::interpreter::Interpreter;
+use super::io::Io;
+use super::report::Termination;
+use super::snapshot::Snapshot;
+use crate::bytecode::ByteCode;
+use crate::config::Value;
+
+/// Runs a program a few instructions at a time, so that a host can interleave
+/// it with its own work and inspect or change its state between steps.
+///
+/// The interpreter options are set on an `Interpreter` converted with
+/// `Vm::from`.
+#[derive(Debug)]
+pub struct Vm<'a> {
+    interpreter: Interpreter<'a>,
+}
+
+impl Vm<'static> {
+    pub fn new(bytecode: ByteCode) -> Self {
+        Vm::from(Interpreter::new(bytecode))
+    }
+}
+
+impl<'a> From<Interpreter<'a>> for Vm<'a> {
+    fn from(interpreter: Interpreter<'a>) -> Self {
+        Vm { interpreter }
+    }
+}
+
+impl<'a> Vm<'a> {
+    pub fn with_io(bytecode: ByteCode, io: Io<'a>) -> Self {
+        Vm::from(Interpreter::with_io(bytecode, io))
+    }
+    /// Registers a host function, see `Interpreter::register_host`.
+    pub fn register_host<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: FnMut(&[Value]) -> Result<Vec<Value>, String> + 'a,
+    {
+        self.interpreter.register_host(name, arity, function);
+    }
+    /// Binds the program parameters, see `Interpreter::set_args`.
+    pub fn set_args(&mut self, args: &[(String, Value)]) -> Result<(), String> {
+        self.interpreter.set_args(args)
+    }
+    /// Executes one instruction and returns whether the program is still
+    /// running. An uncaught error halts the program and is returned by the
+    /// step that raised it; stepping a halted program does nothing.
+    pub fn step(&mut self) -> Result<bool, String> {
+        self.interpreter.step()
+    }
+    /// Executes up to `n_steps` instructions, stopping early if the program
+    /// halts. Returns whether the program is still running.
+    pub fn run_for(&mut self, n_steps: usize) -> Result<bool, String> {
+        for _ in 0..n_steps {
+            if !self.step()? {
+                return Ok(false);
+            }
+        }
+        Ok(!self.is_halted())
+    }
+    /// Serializes the execution state: instruction pointer, stack,
+    /// variables, TRY blocks, random generator and step counts. The snapshot
+    /// records a hash of the program, and can be restored into a `Vm` of the
+    /// same program, possibly in another process. Fails if the state is too
+    /// large for the snapshot format.
+    pub fn snapshot(&self) -> Result<Vec<u8>, String> {
+        self.interpreter.snapshot()?.encode()
+    }
+    /// Replaces the execution state with a snapshot. A snapshot taken from
+    /// another program is refused. Options and host functions are not part
+    /// of the snapshot and are kept.
+    pub fn restore(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let snapshot = Snapshot::decode(bytes)?;
+        self.interpreter.restore(snapshot)
+    }
+    pub fn is_halted(&self) -> bool {
+        self.interpreter.termination().is_some()
+    }
+    /// How the program ended, or `None` while it is running.
+    pub fn termination(&self) -> Option<&Termination> {
+        self.interpreter.termination()
+    }
+    /// Value passed to `RETURN_VALUE`, once the program has returned.
+    pub fn result(&self) -> Option<Value> {
+        self.interpreter.result()
+    }
+    pub fn steps(&self) -> usize {
+        self.interpreter.steps()
+    }
+    /// Values on the stack, the top last.
+    pub fn stack(&self) -> &[Value] {
+        self.interpreter.stack()
+    }
+    /// Returns the value of a variable visible at the end of the program.
+    /// Variables declared in `.scope` blocks cannot be accessed by name.
+    pub fn get_var(&self, name: &str) -> Option<Value> {
+        self.interpreter.get_var(name)
+    }
+    pub fn set_var(&mut self, name: &str, value: Value) -> Result<(), String> {
+        self.interpreter.set_var(name, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_vm(code: &str) -> Vm<'static> {
+        let mut reader = code.as_bytes();
+        Vm::new(ByteCode::transpile_reader(&mut reader).unwrap())
+    }
+
+    #[test]
+    fn step_and_inspect() {
+        let mut vm = new_vm("LOAD_VAL 2 | WRITE_VAR x | READ_VAR x | DUP | ADD | RETURN_VALUE");
+        assert_eq!(vm.step(), Ok(true));
+        assert_eq!(vm.stack(), &[2]);
+        assert_eq!(vm.run_for(3), Ok(true));
+        assert_eq!(vm.stack(), &[2, 2]);
+        assert_eq!(vm.get_var("x"), Some(2));
+        assert!(!vm.is_halted());
+        assert_eq!(vm.run_for(10), Ok(false));
+        assert_eq!(vm.termination(), Some(&Termination::Returned));
+        assert_eq!(vm.result(), Some(4));
+        assert_eq!(vm.steps(), 6);
+        assert_eq!(vm.step(), Ok(false));
+        assert_eq!(vm.steps(), 6);
+    }
+
+    #[test]
+    fn set_var_between_steps() {
+        let mut vm = new_vm("LOAD_VAL 1 | WRITE_VAR x | READ_VAR x | RETURN_VALUE");
+        assert_eq!(vm.run_for(2), Ok(true));
+        assert_eq!(vm.set_var("x", 42), Ok(()));
+        assert_eq!(vm.set_var("y", 1), Err("unknown variable y".to_string()));
+        assert_eq!(vm.get_var("y"), None);
+        assert_eq!(vm.run_for(2), Ok(false));
+        assert_eq!(vm.result(), Some(42));
+    }
+
+    #[test]
+    fn halt_on_error_and_end() {
+        let mut vm = new_vm("LOAD_VAL 1 | ADD");
+        assert_eq!(vm.step(), Ok(true));
+        let error =
+            "Runtime error: unable to process current instruction, ip = 0x01: no value on stack";
+        assert_eq!(vm.step(), Err(error.to_string()));
+        assert_eq!(
+            vm.termination(),
+            Some(&Termination::Error(error.to_string()))
+        );
+        assert_eq!(vm.step(), Ok(false));
+
+        let mut vm = new_vm("LOAD_VAL 1 | POP");
+        assert_eq!(vm.run_for(2), Ok(false));
+        assert_eq!(vm.termination(), Some(&Termination::FellOffEnd));
+        assert_eq!(vm.result(), None);
+    }
+
+    #[test]
+    fn snapshot_and_restore() {
+        let code = concat!(
+            "LOAD_VAL 0 | WRITE_VAR i\n",
+            "&loop\n",
+            "READ_VAR i | LOAD_VAL 1 | ADD | DUP | WRITE_VAR i\n",
+            "LOAD_VAL 100 | RAND | POP\n",
+            "LOAD_VAL 5 | TEST_GT | GOTO &loop\n",
+            "LOAD_VAL 100 | RAND | RETURN_VALUE\n",
+        );
+        let mut vm = new_vm(code);
+        assert_eq!(vm.run_for(12), Ok(true));
+        let snapshot = vm.snapshot().unwrap();
+        assert_eq!(vm.run_for(1000), Ok(false));
+
+        let mut resumed = new_vm(code);
+        assert_eq!(resumed.restore(&snapshot), Ok(()));
+        assert_eq!(resumed.steps(), 12);
+        assert_eq!(resumed.run_for(1000), Ok(false));
+        assert_eq!(resumed.result(), vm.result());
+        assert_eq!(resumed.steps(), vm.steps());
+        assert_eq!(resumed.get_var("i"), Some(5));
+    }
+
+    #[test]
+    fn restore_other_program() {
+        let snapshot = new_vm("LOAD_VAL 1 | RETURN_VALUE").snapshot().unwrap();
+        let mut vm = new_vm("LOAD_VAL 2 | RETURN_VALUE");
+        assert_eq!(
+            vm.restore(&snapshot),
+            Err("Invalid snapshot: taken from a different program".to_string())
+        );
+        assert_eq!(
+            vm.restore(b"MORS"),
+            Err("Invalid snapshot: unexpected end of data".to_string())
+        );
+        assert_eq!(vm.run_for(2), Ok(false));
+        assert_eq!(vm.result(), Some(2));
+    }
+
+    #[test]
+    fn snapshot_long_error() {
+        let code = concat!(
+            "LOAD_VAL 0 | WRITE_VAR i\n",
+            "&loop\n",
+            "READ_VAR i | LOAD_VAL 1 | ADD | DUP | WRITE_VAR i\n",
+            "DUP | LOAD_VAL 30000 | TEST_GT | GOTO &loop\n",
+            "LOAD_VAL 0 | ASSERT \"full\"\n",
+        );
+        let mut vm = new_vm(code);
+        while let Ok(true) = vm.run_for(10000) {}
+        let error = match vm.termination() {
+            Some(Termination::Error(e)) => e.clone(),
+            other => panic!("unexpected termination {:?}", other),
+        };
+        assert!(error.len() > usize::from(u16::MAX));
+
+        let mut resumed = new_vm(code);
+        assert_eq!(resumed.restore(&vm.snapshot().unwrap()), Ok(()));
+        assert_eq!(resumed.termination(), Some(&Termination::Error(error)));
+        assert_eq!(resumed.stack().len(), 30000);
+    }
+}
@@ -1,12 +1,33 @@
 mod bytecode;
 mod config;
+pub mod fmt;
 mod interpreter;
+mod reader;
+pub mod testing;
 
-use bytecode::ByteCode;
-use config::Value;
-use interpreter::interpret as do_interpret;
+pub use bytecode::ByteCode;
+pub use config::Value;
+use interpreter::{
+    interpret as do_interpret, interpret_with_args as do_interpret_with_args,
+    interpret_with_io as do_interpret_with_io,
+};
+pub use interpreter::{ExecutionReport, Interpreter, Io, Termination, Vm};
 
 pub fn interpret(source_file: &str) -> Result<Option<Value>, String> {
     let bytecode = ByteCode::transpile(source_file)?;
     do_interpret(bytecode)
 }
+
+pub fn interpret_with_io(source_file: &str, io: Io) -> Result<Option<Value>, String> {
+    let bytecode = ByteCode::transpile(source_file)?;
+    do_interpret_with_io(bytecode, io)
+}
+
+/// Interprets the program with the values of its `.param` declarations.
+pub fn interpret_with_args(
+    source_file: &str,
+    args: &[(String, Value)],
+) -> Result<Option<Value>, String> {
+    let bytecode = ByteCode::transpile(source_file)?;
+    do_interpret_with_args(bytecode, args)
+}
@@ -1,19 +1,475 @@
-use clap::{arg, Command};
-use morango::interpret;
+use clap::{arg, ArgMatches, Command};
+use morango::fmt::{format_source, is_formatted};
+use morango::testing::{find_tests, run_test};
+use morango::{ByteCode, Interpreter, Value};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+const EXIT_SUCCESS: i32 = 0;
+const EXIT_NOT_FORMATTED: i32 = 1;
+const EXIT_TESTS_FAILED: i32 = 1;
+const EXIT_USAGE_ERROR: i32 = 2;
+const EXIT_NO_RETURN: i32 = 3;
+const EXIT_PARSE_ERROR: i32 = 4;
+const EXIT_RUNTIME_ERROR: i32 = 5;
+
+const STDIN: &str = "-";
+
+struct Outcome {
+    result: Option<Value>,
+    steps: usize,
+    error: Option<String>,
+    exit_code: i32,
+}
+
+impl Outcome {
+    fn failed(error: String, steps: usize, exit_code: i32) -> Self {
+        Outcome {
+            result: None,
+            steps,
+            error: Some(error),
+            exit_code,
+        }
+    }
+
+    fn to_json(&self) -> String {
+        let result = match self.result {
+            Some(v) => v.to_string(),
+            None => "null".to_string(),
+        };
+        let error = match &self.error {
+            Some(e) => json_string(e),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"result\": {}, \"steps\": {}, \"error\": {}}}",
+            result, self.steps, error
+        )
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn parse_arg(s: &str) -> Result<(String, Value), String> {
+    let (name, value) = match s.split_once('=') {
+        Some(pair) => pair,
+        None => return Err(format!("invalid argument `{}`: expected name=value", s)),
+    };
+    match value.parse::<Value>() {
+        Ok(v) => Ok((name.to_string(), v)),
+        Err(e) => Err(format!("invalid argument `{}`: {}", s, e)),
+    }
+}
+
+fn display_name(path: &str) -> &str {
+    if path == STDIN {
+        "<stdin>"
+    } else {
+        path
+    }
+}
+
+fn read_stdin() -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    match io::stdin().read_to_end(&mut bytes) {
+        Ok(_) => Ok(bytes),
+        Err(e) => Err(format!("Unable to read stdin: {}", e)),
+    }
+}
+
+/// Loads a source or compiled program from a file or, for `-`, from stdin.
+fn load_program(path: &str, include_dirs: &[PathBuf]) -> Result<ByteCode, String> {
+    if path != STDIN {
+        return ByteCode::load_with_includes(path, include_dirs);
+    }
+    let bytes = read_stdin()?;
+    if bytes.is_empty() {
+        return Err("Empty file".to_string());
+    }
+    if ByteCode::is_binary(&bytes) {
+        ByteCode::from_bytes(&bytes)
+    } else {
+        ByteCode::transpile_reader_with_includes(&mut bytes.as_slice(), include_dirs)
+    }
+}
+
+fn run(
+    path: &str,
+    include_dirs: &[PathBuf],
+    args: &[(String, Value)],
+    strict: bool,
+    checked: bool,
+    seed: Option<u64>,
+) -> Outcome {
+    let bytecode = match load_program(path, include_dirs) {
+        Ok(bytecode) => bytecode,
+        Err(e) => return Outcome::failed(e, 0, EXIT_PARSE_ERROR),
+    };
+    let mut interpreter = Interpreter::new(bytecode);
+    interpreter.set_strict(strict);
+    interpreter.set_checked(checked);
+    if let Some(seed) = seed {
+        interpreter.set_seed(seed);
+    }
+    if let Err(e) = interpreter.set_args(args) {
+        return Outcome::failed(e, 0, EXIT_USAGE_ERROR);
+    }
+    match interpreter.interpret() {
+        Ok(result) => Outcome {
+            result,
+            steps: interpreter.steps(),
+            error: None,
+            exit_code: match result {
+                Some(_) => EXIT_SUCCESS,
+                None => EXIT_NO_RETURN,
+            },
+        },
+        Err(e) => Outcome::failed(e, interpreter.steps(), EXIT_RUNTIME_ERROR),
+    }
+}
+
+fn files_of(matches: &ArgMatches) -> Vec<String> {
+    match matches.values_of("FILES") {
+        Some(values) => values.map(|s| s.to_string()).collect(),
+        None => Vec::new(),
+    }
+}
+
+fn include_dirs_of(matches: &ArgMatches) -> Vec<PathBuf> {
+    match matches.values_of("include") {
+        Some(values) => values.map(PathBuf::from).collect(),
+        None => Vec::new(),
+    }
+}
+
+fn cmd_run(matches: &ArgMatches, files: &[String]) -> i32 {
+    let include_dirs = include_dirs_of(matches);
+    let json = matches.value_of("format") == Some("json");
+    let strict = matches.is_present("strict");
+    let checked = matches.is_present("checked");
+    let seed = match matches.value_of("seed").map(str::parse::<u64>).transpose() {
+        Ok(seed) => seed,
+        Err(e) => {
+            eprintln!("Error: invalid seed: {}", e);
+            return EXIT_USAGE_ERROR;
+        }
+    };
+    let args = match matches.values_of("arg") {
+        Some(values) => values.map(parse_arg).collect::<Result<Vec<_>, _>>(),
+        None => Ok(Vec::new()),
+    };
+    let args = match args {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return EXIT_USAGE_ERROR;
+        }
+    };
+    let mut exit_code = EXIT_SUCCESS;
+    for file in files {
+        let outcome = run(file, &include_dirs, &args, strict, checked, seed);
+        let prefix = if files.len() > 1 {
+            format!("{}: ", display_name(file))
+        } else {
+            String::new()
+        };
+        if json {
+            println!("{}", outcome.to_json());
+        } else if let Some(e) = &outcome.error {
+            eprintln!("{}Error: {}", prefix, e);
+        } else if let Some(v) = outcome.result {
+            println!("{}{}", prefix, v);
+        } else {
+            eprintln!("{}Program finished without returning a value", prefix);
+        }
+        if exit_code == EXIT_SUCCESS {
+            exit_code = match outcome.result {
+                Some(v) if matches.is_present("exit-with-result") => i32::from(v % 256),
+                _ => outcome.exit_code,
+            };
+        }
+    }
+    exit_code
+}
+
+fn cmd_check(matches: &ArgMatches, files: &[String]) -> i32 {
+    let include_dirs = include_dirs_of(matches);
+    let mut exit_code = EXIT_SUCCESS;
+    for file in files {
+        match load_program(file, &include_dirs).and_then(|bytecode| bytecode.verify()) {
+            Ok(()) => println!("{}: ok", display_name(file)),
+            Err(e) => {
+                eprintln!("{}: {}", display_name(file), e);
+                exit_code = EXIT_PARSE_ERROR;
+            }
+        }
+    }
+    exit_code
+}
+
+fn cmd_disasm(matches: &ArgMatches, files: &[String]) -> i32 {
+    let include_dirs = include_dirs_of(matches);
+    let mut exit_code = EXIT_SUCCESS;
+    for file in files {
+        match load_program(file, &include_dirs) {
+            Ok(bytecode) => {
+                if files.len() > 1 {
+                    println!("; {}", display_name(file));
+                }
+                print!("{}", bytecode.disassemble());
+            }
+            Err(e) => {
+                eprintln!("{}: {}", display_name(file), e);
+                exit_code = EXIT_PARSE_ERROR;
+            }
+        }
+    }
+    exit_code
+}
+
+fn compile(file: &str, include_dirs: &[PathBuf], output: Option<&str>) -> Result<(), String> {
+    let bytes = load_program(file, include_dirs)?.to_bytes()?;
+    let output = match output {
+        Some(output) => output.to_string(),
+        None if file == STDIN => STDIN.to_string(),
+        None => Path::new(file)
+            .with_extension("morc")
+            .to_string_lossy()
+            .into_owned(),
+    };
+    let written = if output == STDIN {
+        io::stdout().write_all(&bytes)
+    } else {
+        fs::write(&output, bytes)
+    };
+    written.map_err(|e| format!("Unable to write {}: {}", display_name(&output), e))
+}
+
+fn cmd_compile(matches: &ArgMatches, files: &[String]) -> i32 {
+    let output = matches.value_of("output");
+    if output.is_some() && files.len() > 1 {
+        eprintln!("Error: --output can only be used with a single file");
+        return EXIT_USAGE_ERROR;
+    }
+    let include_dirs = include_dirs_of(matches);
+    let mut exit_code = EXIT_SUCCESS;
+    for file in files {
+        if let Err(e) = compile(file, &include_dirs, output) {
+            eprintln!("{}: {}", display_name(file), e);
+            exit_code = EXIT_PARSE_ERROR;
+        }
+    }
+    exit_code
+}
+
+fn read_source(file: &str) -> Result<String, String> {
+    if file == STDIN {
+        String::from_utf8(read_stdin()?).map_err(|e| e.to_string())
+    } else {
+        fs::read_to_string(file).map_err(|e| format!("Unable to open file: {}", e))
+    }
+}
+
+fn format_file(file: &str) -> Result<(), String> {
+    let source = read_source(file)?;
+    let formatted = format_source(&source)?;
+    if file == STDIN {
+        print!("{}", formatted);
+        return Ok(());
+    }
+    if formatted != source {
+        fs::write(file, formatted).map_err(|e| format!("Unable to write file: {}", e))?;
+    }
+    Ok(())
+}
+
+fn cmd_fmt(matches: &ArgMatches, files: &[String]) -> i32 {
+    let check = matches.is_present("check");
+    let mut exit_code = EXIT_SUCCESS;
+    for file in files {
+        let result = if check {
+            read_source(file).and_then(|source| is_formatted(&source))
+        } else {
+            format_file(file).map(|_| true)
+        };
+        match result {
+            Ok(true) => {}
+            Ok(false) => {
+                println!("{}: not formatted", display_name(file));
+                if exit_code == EXIT_SUCCESS {
+                    exit_code = EXIT_NOT_FORMATTED;
+                }
+            }
+            Err(e) => {
+                eprintln!("{}: {}", display_name(file), e);
+                exit_code = EXIT_PARSE_ERROR;
+            }
+        }
+    }
+    exit_code
+}
+
+fn cmd_test(matches: &ArgMatches, paths: &[String]) -> i32 {
+    let include_dirs = include_dirs_of(matches);
+    let mut tests = Vec::new();
+    for path in paths {
+        match find_tests(Path::new(path)) {
+            Ok(found) => tests.extend(found),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                return EXIT_USAGE_ERROR;
+            }
+        }
+    }
+    let mut failed = 0;
+    for test in &tests {
+        match run_test(test, &include_dirs) {
+            Ok(()) => println!("{}: ok", test.display()),
+            Err(e) => {
+                println!("{}: FAILED: {}", test.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    println!("{} passed, {} failed", tests.len() - failed, failed);
+    if failed > 0 {
+        EXIT_TESTS_FAILED
+    } else {
+        EXIT_SUCCESS
+    }
+}
+
+fn files_arg() -> clap::Arg<'static> {
+    arg!([FILES] "Files to process, `-` reads stdin").multiple_values(true)
+}
+
+fn include_arg() -> clap::Arg<'static> {
+    arg!(-I --include <DIR> "Directory searched for .include files")
+        .required(false)
+        .multiple_occurrences(true)
+}
+
+fn main() {
     let matches = Command::new("Morango interpreter")
         .version("0.1.0")
         .author("Alex Mikhalevich <alex@mikhalevich.com>")
         .about("Interpreter for the toy Morango language")
-        .arg(arg!(
-            -f --file <FILE> "Source code to interpret"
-        ))
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("run")
+                .about("Execute programs")
+                .arg(files_arg())
+                .arg(include_arg())
+                .arg(
+                    arg!(-f --file <FILE> "Program to execute")
+                        .required(false)
+                        .multiple_occurrences(true),
+                )
+                .arg(
+                    arg!(
+                        -a --arg <ARG> "Program parameter declared with .param, given as name=value"
+                    )
+                    .required(false)
+                    .multiple_occurrences(true),
+                )
+                .arg(arg!(
+                    --"exit-with-result" "Use the returned value (modulo 256) as the process exit code"
+                ))
+                .arg(arg!(
+                    --strict "Fail when a GOTO or GOTO_IF_ZERO condition is neither 0 nor 1"
+                ))
+                .arg(arg!(
+                    --checked "Report ADD, MULTIPLY and POW overflows as runtime errors"
+                ))
+                .arg(
+                    arg!(
+                        --seed <SEED> "Seed of the RAND instruction"
+                    )
+                    .required(false),
+                )
+                .arg(
+                    arg!(
+                        --format <FORMAT> "Output format"
+                    )
+                    .required(false)
+                    .possible_values(["text", "json"])
+                    .default_value("text"),
+                ),
+        )
+        .subcommand(
+            Command::new("check")
+                .about("Transpile and verify programs without running them")
+                .arg(files_arg())
+                .arg(include_arg()),
+        )
+        .subcommand(
+            Command::new("disasm")
+                .about("Print bytecode listings")
+                .arg(files_arg())
+                .arg(include_arg()),
+        )
+        .subcommand(
+            Command::new("compile")
+                .about("Write binary bytecode, next to the source by default")
+                .arg(files_arg())
+                .arg(include_arg())
+                .arg(arg!(-o --output <OUTPUT> "Output file, `-` writes stdout").required(false)),
+        )
+        .subcommand(
+            Command::new("test")
+                .about("Run the programs marked with .test in files and directories")
+                .arg(arg!([FILES] "Test files, or directories searched for .mor tests").multiple_values(true))
+                .arg(include_arg()),
+        )
+        .subcommand(
+            Command::new("fmt")
+                .about("Format source files in place")
+                .arg(files_arg())
+                .arg(arg!(
+                    --check "Only report files that are not formatted, exiting with code 1"
+                )),
+        )
         .get_matches();
-    let source_file = matches
-        .value_of("file")
-        .expect("You should specify a file to interpret");
-    let result = interpret(source_file)?;
-    println!("{}", result.unwrap());
-    Ok(())
+
+    let (name, sub_matches) = matches.subcommand().unwrap();
+    let mut files = files_of(sub_matches);
+    if name == "run" {
+        if let Some(values) = sub_matches.values_of("file") {
+            files.extend(values.map(|s| s.to_string()));
+        }
+    }
+    let exit_code = if files.is_empty() {
+        eprintln!("Error: no input files");
+        EXIT_USAGE_ERROR
+    } else {
+        match name {
+            "run" => cmd_run(sub_matches, &files),
+            "check" => cmd_check(sub_matches, &files),
+            "disasm" => cmd_disasm(sub_matches, &files),
+            "compile" => cmd_compile(sub_matches, &files),
+            "fmt" => cmd_fmt(sub_matches, &files),
+            "test" => cmd_test(sub_matches, &files),
+            _ => unreachable!("unknown subcommand {}", name),
+        }
+    };
+    process::exit(exit_code);
 }
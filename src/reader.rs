@@ -0,0 +1,66 @@
+//! Cursor over the little-endian binary formats, the compiled bytecode and
+//! the `Vm` snapshots. Errors start with the prefix naming the format, e.g.
+//! `Invalid bytecode: unexpected end of data`.
+
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    prefix: &'static str,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8], prefix: &'static str) -> Self {
+        Reader {
+            bytes,
+            pos: 0,
+            prefix,
+        }
+    }
+
+    /// Builds an error message for the format being read.
+    pub fn error(&self, reason: impl std::fmt::Display) -> String {
+        format!("{}: {}", self.prefix, reason)
+    }
+
+    pub fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.pos + n > self.bytes.len() {
+            return Err(self.error("unexpected end of data"));
+        }
+        let chunk = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(chunk)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, String> {
+        let chunk = self.take(2)?;
+        Ok(u16::from_le_bytes([chunk[0], chunk[1]]))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// Reads `len` bytes of UTF-8 text; `what` names it in the error.
+    pub fn read_utf8(&mut self, len: usize, what: &str) -> Result<String, String> {
+        match String::from_utf8(self.take(len)?.to_vec()) {
+            Ok(s) => Ok(s),
+            Err(_) => Err(self.error(format!("{} is not valid UTF-8", what))),
+        }
+    }
+
+    /// Fails if there are bytes left after the last field.
+    pub fn finish(&self) -> Result<(), String> {
+        if self.pos != self.bytes.len() {
+            return Err(self.error("trailing data"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+//! Runs Morango test programs.
+//!
+//! A test is a file that contains the `.test` directive or declares its
+//! expected outcome in comments at the top of the file:
+//!
+//! ```text
+//! ; expect: 600
+//! ; expect: none
+//! ; expect-error: undeclared variable x
+//! ```
+//!
+//! `expect` gives the returned value, `none` meaning that the program finishes
+//! without `RETURN_VALUE`, and `expect-error` a part of the error message.
+//! Without an expectation a test passes when it runs to completion and fails
+//! on any transpilation or runtime error, such as a failed `ASSERT`.
+//!
+//! Tests get no input. Their output is captured and only shown, after the
+//! error, when they fail.
+
+use crate::bytecode::{lexer, ByteCode};
+use crate::config::Value;
+use crate::interpreter::{Interpreter, Io};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const SOURCE_EXTENSION: &str = "mor";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expectation {
+    Result(Option<Value>),
+    Error(String),
+}
+
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expectation::Result(Some(v)) => write!(f, "{}", v),
+            Expectation::Result(None) => write!(f, "none"),
+            Expectation::Error(e) => write!(f, "error: {}", e),
+        }
+    }
+}
+
+/// Reads the expectation from the comment lines that start the source.
+fn parse_expectation(source: &str) -> Result<Option<Expectation>, String> {
+    let mut expectation = None;
+    for line in source.lines() {
+        let (code, comment) = lexer::split_comment(line);
+        if !code.trim().is_empty() {
+            break;
+        }
+        let header = comment.map(|c| c.trim_start_matches([';', '#']).trim());
+        let (key, value) = match header.and_then(|h| h.split_once(':')) {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => continue,
+        };
+        let parsed = match key {
+            "expect" if value == "none" => Expectation::Result(None),
+            "expect" => match value.parse::<Value>() {
+                Ok(v) => Expectation::Result(Some(v)),
+                Err(e) => return Err(format!("invalid expected result `{}`: {}", value, e)),
+            },
+            "expect-error" => Expectation::Error(value.to_string()),
+            _ => continue,
+        };
+        if expectation.is_some() {
+            return Err("the expected outcome is declared more than once".to_string());
+        }
+        expectation = Some(parsed);
+    }
+    Ok(expectation)
+}
+
+/// Returns `true` if the source declares `.test` or an expected outcome.
+pub fn is_test(source: &str) -> bool {
+    !matches!(parse_expectation(source), Ok(None))
+        || source.lines().any(|line| {
+            let (code, _comment) = lexer::split_comment(line);
+            lexer::split_statements(code)
+                .iter()
+                .any(|statement| statement.split_whitespace().next() == Some(".test"))
+        })
+}
+
+/// Collects the test files at `path`: the file itself if it is a test, or the
+/// `.mor` tests found recursively in a directory, sorted by path.
+pub fn find_tests(path: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut tests = Vec::new();
+    if path.is_dir() {
+        collect_tests(path, &mut tests)?;
+        tests.sort();
+    } else if is_test(&read_source(path)?) {
+        tests.push(path.to_path_buf());
+    }
+    Ok(tests)
+}
+
+fn collect_tests(dir: &Path, tests: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => return Err(format!("Unable to read {}: {}", dir.display(), e)),
+    };
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => return Err(format!("Unable to read {}: {}", dir.display(), e)),
+        };
+        if path.is_dir() {
+            collect_tests(&path, tests)?;
+        } else if path.extension().is_some_and(|ext| ext == SOURCE_EXTENSION)
+            && is_test(&read_source(&path)?)
+        {
+            tests.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn read_source(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("Unable to open file: {}", e))
+}
+
+/// Transpiles and runs a test, comparing the outcome with the expected one.
+pub fn run_test(path: &Path, include_dirs: &[PathBuf]) -> Result<(), String> {
+    let expectation = parse_expectation(&read_source(path)?)?;
+    let mut output = Vec::new();
+    let outcome = ByteCode::transpile_with_includes(&path.to_string_lossy(), include_dirs)
+        .and_then(|bytecode| {
+            Interpreter::with_io(bytecode, Io::new(io::empty(), &mut output)).interpret()
+        });
+    let error = match check_outcome(expectation, outcome) {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+    if output.is_empty() {
+        return Err(error);
+    }
+    Err(format!(
+        "{}\noutput:\n{}",
+        error,
+        String::from_utf8_lossy(&output).trim_end()
+    ))
+}
+
+fn check_outcome(
+    expectation: Option<Expectation>,
+    outcome: Result<Option<Value>, String>,
+) -> Result<(), String> {
+    let passed = match (&expectation, &outcome) {
+        (None, outcome) => return outcome.as_ref().map(|_| ()).map_err(|e| e.clone()),
+        (Some(Expectation::Result(expected)), Ok(result)) => expected == result,
+        (Some(Expectation::Error(expected)), Err(e)) => e.contains(expected.as_str()),
+        _ => false,
+    };
+    if passed {
+        return Ok(());
+    }
+    let actual = match outcome {
+        Ok(result) => Expectation::Result(result),
+        Err(e) => Expectation::Error(e),
+    };
+    Err(format!(
+        "unexpected outcome\n- {}\n+ {}",
+        expectation.unwrap(),
+        actual
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_test_annotation() {
+        assert!(is_test(".test\nLOAD_VAL 1\n"));
+        assert!(is_test("LOAD_VAL 1 | .test ; a test\n"));
+        assert!(is_test("; sum\n\n; expect: 3\nLOAD_VAL 3\n"));
+        assert!(!is_test("LOAD_VAL 1 ; .test\n"));
+        assert!(!is_test("PRINT_STR \".test\"\n"));
+        assert!(!is_test("LOAD_VAL 1\n; expect: 1\n"));
+    }
+
+    #[test]
+    fn parse_expectations() {
+        assert_eq!(
+            parse_expectation("; expect: 600\nLOAD_VAL 600"),
+            Ok(Some(Expectation::Result(Some(600))))
+        );
+        assert_eq!(
+            parse_expectation("# expect: none\nLOAD_VAL 600"),
+            Ok(Some(Expectation::Result(None)))
+        );
+        assert_eq!(
+            parse_expectation("; note: not a header\n;expect-error:  no value \nADD"),
+            Ok(Some(Expectation::Error("no value".to_string())))
+        );
+        assert_eq!(
+            parse_expectation("; expect: x"),
+            Err("invalid expected result `x`: invalid digit found in string".to_string())
+        );
+        assert_eq!(
+            parse_expectation("; expect: 1\n; expect-error: x"),
+            Err("the expected outcome is declared more than once".to_string())
+        );
+    }
+
+    #[test]
+    fn output_shown_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let passing = dir.path().join("passing.mor");
+        fs::write(&passing, ".test\nPRINT_STR \"hello\"\n").unwrap();
+        assert_eq!(run_test(&passing, &[]), Ok(()));
+
+        let failing = dir.path().join("failing.mor");
+        fs::write(
+            &failing,
+            "; expect: 2\nPRINT_STR \"hello\"\nLOAD_VAL 1 | PRINT\n",
+        )
+        .unwrap();
+        assert_eq!(
+            run_test(&failing, &[]),
+            Err("unexpected outcome\n- 2\n+ none\noutput:\nhello\n1".to_string())
+        );
+    }
+}
@@ -0,0 +1,344 @@
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use tempfile::{tempdir, NamedTempFile};
+
+fn write_program(code: &str) -> NamedTempFile {
+    let mut file = NamedTempFile::new().expect("Failed to create temp file");
+    write!(file, "{}", code).expect("Failed to write to temp file");
+    file
+}
+
+fn morango(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_morango"))
+        .args(args)
+        .output()
+        .expect("Failed to run the interpreter binary")
+}
+
+fn morango_with_stdin(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_morango"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run the interpreter binary");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin)
+        .expect("Failed to write to stdin");
+    child
+        .wait_with_output()
+        .expect("Failed to wait for the binary")
+}
+
+fn run_cli(code: &str, extra_args: &[&str]) -> Output {
+    let file = write_program(code);
+    let path = file.path().to_str().unwrap();
+    let mut args = vec!["run", "-f", path];
+    args.extend_from_slice(extra_args);
+    morango(&args)
+}
+
+#[test]
+pub fn test_cli_success() {
+    let output = run_cli("LOAD_VAL 4\nRETURN_VALUE\n", &[]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "4\n");
+}
+
+#[test]
+pub fn test_cli_no_return() {
+    let output = run_cli("LOAD_VAL 1\n", &[]);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+}
+
+#[test]
+pub fn test_cli_parse_error() {
+    let output = run_cli("READ_VAR x\n", &[]);
+    assert_eq!(output.status.code(), Some(4));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Error: Transpilation error at line 1: undeclared variable x\n"
+    );
+}
+
+#[test]
+pub fn test_cli_runtime_error() {
+    let output = run_cli("ADD\n", &[]);
+    assert_eq!(output.status.code(), Some(5));
+}
+
+#[test]
+pub fn test_cli_missing_param() {
+    let output = run_cli(".param n\nREAD_VAR n\nRETURN_VALUE\n", &[]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+pub fn test_cli_exit_with_result() {
+    let output = run_cli(
+        ".param n\nREAD_VAR n\nRETURN_VALUE\n",
+        &["--arg", "n=300", "--exit-with-result"],
+    );
+    assert_eq!(output.status.code(), Some(44));
+}
+
+#[test]
+pub fn test_cli_strict() {
+    let code = "LOAD_VAL 2\nGOTO &end\nLOAD_VAL 0\nRETURN_VALUE\n&end\nLOAD_VAL 1\nRETURN_VALUE\n";
+    let output = run_cli(code, &[]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+
+    let output = run_cli(code, &["--strict"]);
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Error: Runtime error: unable to process current instruction, ip = 0x01: condition 2 is not a boolean\n"
+    );
+}
+
+#[test]
+pub fn test_cli_checked() {
+    let output = run_cli(
+        "LOAD_VAL 0xFFFF\nLOAD_VAL 1\nADD\nRETURN_VALUE\n",
+        &["--checked"],
+    );
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Error: Runtime error: unable to process current instruction, ip = 0x02: arithmetic overflow in 65535 + 1\n"
+    );
+}
+
+#[test]
+pub fn test_cli_pow_wraps() {
+    let output = run_cli("LOAD_VAL 3 | LOAD_VAL 11 | POW | RETURN_VALUE\n", &[]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "46075\n");
+
+    let output = run_cli(
+        "LOAD_VAL 2 | LOAD_VAL 20 | POW | RETURN_VALUE\n",
+        &["--checked"],
+    );
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Error: Runtime error: unable to process current instruction, ip = 0x02: arithmetic overflow in 2 ** 20\n"
+    );
+}
+
+#[test]
+pub fn test_cli_seed() {
+    let code = "LOAD_VAL 1000 | RAND | PRINT | LOAD_VAL 1000 | RAND | RETURN_VALUE\n";
+    let first = run_cli(code, &["--seed", "7"]);
+    assert_eq!(first.status.code(), Some(0));
+    let second = run_cli(code, &["--seed", "7"]);
+    assert_eq!(first.stdout, second.stdout);
+
+    let output = run_cli(code, &["--seed", "x"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Error: invalid seed: invalid digit found in string\n"
+    );
+}
+
+#[test]
+pub fn test_cli_json_format() {
+    let output = run_cli(
+        "LOAD_VAL 2\nLOAD_VAL 3\nADD\nRETURN_VALUE\n",
+        &["--format", "json"],
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "{\"result\": 5, \"steps\": 4, \"error\": null}\n"
+    );
+}
+
+#[test]
+pub fn test_cli_json_format_error() {
+    let output = run_cli("LOAD_VAL 1\nADD\n", &["--format", "json"]);
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        concat!(
+            "{\"result\": null, \"steps\": 2, \"error\": \"Runtime error: unable to process ",
+            "current instruction, ip = 0x01: no value on stack\"}\n"
+        )
+    );
+}
+
+#[test]
+pub fn test_cli_run_multiple_files() {
+    let first = write_program("LOAD_VAL 1\nRETURN_VALUE\n");
+    let second = write_program("LOAD_VAL 2\nRETURN_VALUE\n");
+    let first = first.path().to_str().unwrap();
+    let second = second.path().to_str().unwrap();
+    let output = morango(&["run", first, second]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{}: 1\n{}: 2\n", first, second)
+    );
+}
+
+#[test]
+pub fn test_cli_run_stdin() {
+    let output = morango_with_stdin(&["run", "-"], b"LOAD_VAL 7\nRETURN_VALUE\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "7\n");
+}
+
+#[test]
+pub fn test_cli_check() {
+    let valid = write_program("LOAD_VAL 1\nRETURN_VALUE\n");
+    let invalid = write_program("READ_VAR x\n");
+    let valid = valid.path().to_str().unwrap();
+    let invalid = invalid.path().to_str().unwrap();
+    let output = morango(&["check", valid, invalid]);
+    assert_eq!(output.status.code(), Some(4));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{}: ok\n", valid)
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!(
+            "{}: Transpilation error at line 1: undeclared variable x\n",
+            invalid
+        )
+    );
+}
+
+#[test]
+pub fn test_cli_disasm() {
+    let output = morango_with_stdin(&["disasm", "-"], b"LOAD_VAL 7\nWRITE_VAR x\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "; data size: 1\n0000: LOAD_VAL 7\n0001: WRITE_VAR [0x00]\n"
+    );
+}
+
+#[test]
+pub fn test_cli_compile_and_run() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let source = dir.path().join("prog.mor");
+    fs::write(&source, "LOAD_VAL 6\nLOAD_VAL 7\nMULTIPLY\nRETURN_VALUE\n").unwrap();
+    let output = morango(&["compile", source.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+
+    let compiled = dir.path().join("prog.morc");
+    assert!(fs::read(&compiled).unwrap().starts_with(b"MORB"));
+    let output = morango(&["run", compiled.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "42\n");
+}
+
+#[test]
+pub fn test_cli_compile_too_large() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let source = dir.path().join("prog.mor");
+    fs::write(&source, format!("PRINT_STR \"{}\"\n", "x".repeat(70_000))).unwrap();
+    let output = morango(&["compile", source.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .ends_with("Unable to encode bytecode: string length 70000 is too large\n"));
+    assert!(!dir.path().join("prog.morc").exists());
+}
+
+#[test]
+pub fn test_cli_fmt() {
+    let file = write_program("  load_val   1\n\treturn_value\n");
+    let path = file.path().to_str().unwrap();
+    let output = morango(&["fmt", path]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        "LOAD_VAL 1\nRETURN_VALUE\n"
+    );
+}
+
+#[test]
+pub fn test_cli_fmt_check() {
+    let formatted = write_program("&start\n    LOAD_VAL 1\n    RETURN_VALUE\n");
+    let unformatted = write_program("&start\nLOAD_VAL 1\nRETURN_VALUE\n");
+    let formatted = formatted.path().to_str().unwrap();
+    let unformatted = unformatted.path().to_str().unwrap();
+    let output = morango(&["fmt", "--check", formatted, unformatted]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{}: not formatted\n", unformatted)
+    );
+    assert_eq!(
+        fs::read_to_string(unformatted).unwrap(),
+        "&start\nLOAD_VAL 1\nRETURN_VALUE\n"
+    );
+}
+
+#[test]
+pub fn test_cli_include_dir() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    fs::write(dir.path().join("answer.mor"), ".const ANSWER 42\n").unwrap();
+    let file = write_program(".include \"answer.mor\"\nLOAD_VAL ANSWER\nRETURN_VALUE\n");
+    let path = file.path().to_str().unwrap();
+
+    let output = morango(&["run", path]);
+    assert_eq!(output.status.code(), Some(4));
+    let output = morango(&["run", "-I", dir.path().to_str().unwrap(), path]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "42\n");
+}
+
+#[test]
+pub fn test_cli_test() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir(dir.path().join("math")).unwrap();
+    fs::write(
+        dir.path().join("math").join("add.mor"),
+        ".test\nPRINT_STR \"adding\"\nLOAD_VAL 2 | LOAD_VAL 3 | ADD\nLOAD_VAL 5 | TEST_EQ | ASSERT \"2 + 3 = 5\"\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("mult.mor"),
+        ".test\nPRINT_STR \"multiplying\"\nLOAD_VAL 9\nLOAD_VAL 2 | LOAD_VAL 3 | MULTIPLY\nLOAD_VAL 5 | TEST_EQ | ASSERT \"2 * 3 = 5\"\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("program.mor"), "ADD\n").unwrap();
+    let root = dir.path().to_str().unwrap();
+
+    let output = morango(&["test", root]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!(
+            concat!(
+                "{root}/math/add.mor: ok\n",
+                "{root}/mult.mor: FAILED: Runtime error: unable to process current instruction, ",
+                "ip = 0x07: assertion failed at line 5: 2 * 3 = 5, stack: [9]\n",
+                "output:\n",
+                "multiplying\n",
+                "1 passed, 1 failed\n",
+            ),
+            root = root
+        )
+    );
+
+    let add = dir.path().join("math").join("add.mor");
+    let output = morango(&["test", add.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+pub fn test_cli_no_files() {
+    let output = morango(&["check"]);
+    assert_eq!(output.status.code(), Some(2));
+}
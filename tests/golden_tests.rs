@@ -0,0 +1,20 @@
+use morango::testing::{find_tests, run_test};
+use std::path::Path;
+
+#[test]
+pub fn test_golden_files() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden");
+    let tests = find_tests(&dir).expect("Failed to list golden files");
+    assert!(!tests.is_empty());
+    let failures: Vec<String> = tests
+        .iter()
+        .filter_map(|test| {
+            run_test(test, &[])
+                .err()
+                .map(|e| format!("{}: {}", test.display(), e))
+        })
+        .collect();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
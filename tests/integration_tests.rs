@@ -1,48 +1,22 @@
-use morango::interpret;
+use morango::{
+    interpret, interpret_with_args, interpret_with_io, ByteCode, Interpreter, Io, Termination, Vm,
+};
+use std::fs;
 use std::io::Write;
-use tempfile::NamedTempFile;
+use std::path::PathBuf;
+use tempfile::{tempdir, NamedTempFile};
 
 #[test]
-pub fn test_simple_program() {
-    let mut file = NamedTempFile::new().expect("Failed to create temp file");
-    let code = concat!(
-        "LOAD_VAL 1\n",
-        "WRITE_VAR x\n",
-        "\n",
-        "LOAD_VAL 2\n",
-        "WRITE_VAR y\n",
-        "\n",
-        "READ_VAR x\n",
-        "LOAD_VAL 1\n",
-        "ADD\n",
-        "READ_VAR y\n",
-        "MULTIPLY\n",
-        "RETURN_VALUE\n",
-        "\n",
-    );
-    write!(file, "{}", code).expect("Failed to write to temp file");
-
-    let result = interpret(
-        file.path()
-            .to_str()
-            .expect("Failed to convert temp file path to string"),
-    );
-    assert!(result.is_ok());
-    assert!(result.as_ref().ok().is_some());
-    assert_eq!(result.ok().unwrap().unwrap() as u16, 4);
-}
-
-#[test]
-pub fn test_loop_program() {
+pub fn test_overflow() {
     let mut file = NamedTempFile::new().expect("Failed to create temp file");
     /*
-     * x = 20
-     * for i = 0 to 10:
+     * x = 65530
+     * for i in 0..10:
      *  x += 1
-     * return x
+     * return x * 256 * 256 + x
      */
     let code = concat!(
-        "LOAD_VAL 20\n",
+        "LOAD_VAL 65530\n",
         "WRITE_VAR x\n",
         "LOAD_VAL 0\n",
         "&label\n",
@@ -56,7 +30,14 @@ pub fn test_loop_program() {
         "LOAD_VAL 10\n",
         "TEST_GT\n",
         "GOTO &label\n",
+        "POP\n",
+        "READ_VAR x\n",
+        "LOAD_VAL 256\n",
+        "MULTIPLY\n",
+        "LOAD_VAL 256\n",
+        "MULTIPLY\n",
         "READ_VAR x\n",
+        "ADD\n",
         "RETURN_VALUE\n",
     );
     write!(file, "{}", code).expect("Failed to write to temp file");
@@ -66,58 +47,13 @@ pub fn test_loop_program() {
             .to_str()
             .expect("Failed to convert temp file path to string"),
     );
-    assert!(result.is_ok());
-    assert!(result.as_ref().ok().is_some());
-    assert_eq!(result.ok().unwrap().unwrap() as u16, 30);
+    assert_eq!(result, Ok(Some(4)));
 }
 
 #[test]
-pub fn test_nested_loop_program() {
+pub fn test_empty_code() {
     let mut file = NamedTempFile::new().expect("Failed to create temp file");
-    /*
-     * x = 20
-     * y = 0
-     * for i = 0 to 10:
-     *  x += 1
-     *  for j = 1 to 3:
-     *   y += 1
-     * return x * y
-     */
-    let code = concat!(
-        "LOAD_VAL 20\n",
-        "WRITE_VAR x\n",
-        "LOAD_VAL 0\n",
-        "WRITE_VAR y\n",
-        "LOAD_VAL 0\n",
-        "&outer\n",
-        "LOAD_VAL 1\n",
-        "READ_VAR x\n",
-        "ADD\n",
-        "WRITE_VAR x\n",
-        "LOAD_VAL 1\n",
-        "&inner\n",
-        "LOAD_VAL 1\n",
-        "READ_VAR y\n",
-        "ADD\n",
-        "WRITE_VAR y\n",
-        "LOAD_VAL 1\n",
-        "ADD\n",
-        "DUP\n",
-        "LOAD_VAL 3\n",
-        "TEST_GT\n",
-        "GOTO &inner\n",
-        "POP\n",
-        "LOAD_VAL 1\n",
-        "ADD\n",
-        "DUP\n",
-        "LOAD_VAL 10\n",
-        "TEST_GT\n",
-        "GOTO &outer\n",
-        "READ_VAR x\n",
-        "READ_VAR y\n",
-        "MULTIPLY\n",
-        "RETURN_VALUE\n",
-    );
+    let code = "".to_string();
     write!(file, "{}", code).expect("Failed to write to temp file");
 
     let result = interpret(
@@ -125,191 +61,480 @@ pub fn test_nested_loop_program() {
             .to_str()
             .expect("Failed to convert temp file path to string"),
     );
-    assert!(result.is_ok());
-    assert!(result.as_ref().ok().is_some());
-    assert_eq!(result.ok().unwrap().unwrap() as u16, 600);
+    assert_eq!(result, Err("Empty file".to_string()));
 }
 
 #[test]
-#[should_panic]
-pub fn test_overflow() {
+pub fn test_console_io() {
     let mut file = NamedTempFile::new().expect("Failed to create temp file");
-    /*
-     * x = 20
-     * while 1 < 10:
-     *  x += 1
-     * return x
-     */
     let code = concat!(
-        "LOAD_VAL 20\n",
-        "WRITE_VAR x\n",
-        "LOAD_VAL 0\n",
-        "&label\n",
-        "LOAD_VAL 1\n",
-        "READ_VAR x\n",
-        "ADD\n",
-        "WRITE_VAR x\n",
-        "LOAD_VAL 1\n",
-        "ADD\n",
-        "LOAD_VAL 1\n",
-        "LOAD_VAL 10\n",
-        "TEST_GT\n",
-        "GOTO &label\n",
-        "READ_VAR x\n",
+        "PRINT_STR \"enter two numbers:\"\n",
+        "READ_INPUT\n",
+        "READ_INPUT\n",
+        "MULTIPLY\n",
+        "DUP\n",
+        "PRINT_STR \"product:\"\n",
+        "PRINT\n",
         "RETURN_VALUE\n",
     );
     write!(file, "{}", code).expect("Failed to write to temp file");
 
-    let _ret = interpret(
+    let mut output = Vec::new();
+    let result = interpret_with_io(
         file.path()
             .to_str()
             .expect("Failed to convert temp file path to string"),
+        Io::new("6\n7\n".as_bytes(), &mut output),
+    );
+    assert_eq!(result, Ok(Some(42)));
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "enter two numbers:\nproduct:\n42\n"
     );
 }
 
-#[test]
-pub fn test_early_return() {
+fn write_params_program() -> NamedTempFile {
     let mut file = NamedTempFile::new().expect("Failed to create temp file");
     let code = concat!(
-        "LOAD_VAL 1\n",
-        "WRITE_VAR x\n",
-        "\n",
-        "LOAD_VAL 2\n",
-        "WRITE_VAR y\n",
-        "\n",
-        "READ_VAR x\n",
-        "LOAD_VAL 1\n",
-        "ADD\n",
-        "RETURN_VALUE\n",
-        "READ_VAR y\n",
+        ".param n\n",
+        ".param m\n",
+        "READ_VAR n\n",
+        "READ_VAR m\n",
         "MULTIPLY\n",
-        "\n",
+        "RETURN_VALUE\n",
     );
     write!(file, "{}", code).expect("Failed to write to temp file");
+    file
+}
 
-    let result = interpret(
+#[test]
+pub fn test_params() {
+    let file = write_params_program();
+    let result = interpret_with_args(
         file.path()
             .to_str()
             .expect("Failed to convert temp file path to string"),
+        &[("m".to_string(), 3), ("n".to_string(), 10)],
     );
-    assert!(result.is_ok());
-    assert!(result.as_ref().ok().is_some());
-    assert_eq!(result.ok().unwrap().unwrap() as u16, 2);
+    assert_eq!(result, Ok(Some(30)));
 }
 
 #[test]
-pub fn test_no_return() {
-    let mut file = NamedTempFile::new().expect("Failed to create temp file");
-    let code = concat!("LOAD_VAL 1\n", "LOAD_VAL 2\n", "ADD\n",);
-    write!(file, "{}", code).expect("Failed to write to temp file");
-
-    let result = interpret(
+pub fn test_params_missing() {
+    let file = write_params_program();
+    let result = interpret_with_args(
         file.path()
             .to_str()
             .expect("Failed to convert temp file path to string"),
+        &[("n".to_string(), 10)],
+    );
+    assert_eq!(
+        result,
+        Err("Invalid arguments: missing parameter m".to_string())
     );
-    assert!(result.is_ok());
-    assert!(result.as_ref().ok().unwrap().is_none());
 }
 
 #[test]
-pub fn test_empty_code() {
-    let mut file = NamedTempFile::new().expect("Failed to create temp file");
-    let code = "".to_string();
-    write!(file, "{}", code).expect("Failed to write to temp file");
-
+pub fn test_params_not_given() {
+    let file = write_params_program();
     let result = interpret(
         file.path()
             .to_str()
             .expect("Failed to convert temp file path to string"),
     );
-    assert_eq!(result, Err("Empty file".to_string()));
+    assert_eq!(
+        result,
+        Err("Invalid arguments: missing parameter n".to_string())
+    );
 }
 
 #[test]
-pub fn test_invalid_code_undeclared_var() {
-    let mut file = NamedTempFile::new().expect("Failed to create temp file");
-    let code = "READ_VAR x".to_string();
-    write!(file, "{}", code).expect("Failed to write to temp file");
-
-    let result = interpret(
+pub fn test_params_unknown() {
+    let file = write_params_program();
+    let result = interpret_with_args(
         file.path()
             .to_str()
             .expect("Failed to convert temp file path to string"),
+        &[
+            ("n".to_string(), 10),
+            ("m".to_string(), 3),
+            ("k".to_string(), 1),
+        ],
     );
     assert_eq!(
         result,
-        Err("Transpilation error at line 1: undeclared variable x".to_string())
+        Err("Invalid arguments: unknown parameter k".to_string())
     );
 }
 
 #[test]
-pub fn test_invalid_code_empty_stack() {
-    let mut file = NamedTempFile::new().expect("Failed to create temp file");
-    let code = "ADD".to_string();
-    write!(file, "{}", code).expect("Failed to write to temp file");
-
-    let result = interpret(
+pub fn test_params_duplicated() {
+    let file = write_params_program();
+    let result = interpret_with_args(
         file.path()
             .to_str()
             .expect("Failed to convert temp file path to string"),
+        &[
+            ("n".to_string(), 10),
+            ("m".to_string(), 3),
+            ("n".to_string(), 1),
+        ],
     );
     assert_eq!(
         result,
-        Err(
-            "Runtime error: unable to process current instruction, ip = 0x00: no value on stack"
-                .to_string()
-        )
+        Err("Invalid arguments: parameter n is given more than once".to_string())
     );
 }
 
 #[test]
-pub fn test_invalid_code_no_args() {
-    let mut file = NamedTempFile::new().expect("Failed to create temp file");
-    let code = "READ_VAR".to_string();
-    write!(file, "{}", code).expect("Failed to write to temp file");
+pub fn test_include_relative_to_including_file() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir(dir.path().join("lib")).unwrap();
+    fs::write(
+        dir.path().join("lib/math.mor"),
+        ".include \"consts.mor\"\n.macro double\nLOAD_VAL 2 | MULTIPLY\n.endmacro\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("lib/consts.mor"), ".const BASE 21\n").unwrap();
+    let main = dir.path().join("main.mor");
+    fs::write(
+        &main,
+        ".include \"lib/math.mor\"\nLOAD_VAL BASE\ndouble\nRETURN_VALUE\n",
+    )
+    .unwrap();
 
-    let result = interpret(
-        file.path()
-            .to_str()
-            .expect("Failed to convert temp file path to string"),
-    );
+    let result = interpret(main.to_str().unwrap());
+    assert_eq!(result, Ok(Some(42)));
+}
+
+#[test]
+pub fn test_include_error_location() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    fs::write(dir.path().join("lib.mor"), "LOAD_VAL 1\nREAD_VAR y\n").unwrap();
+    let main = dir.path().join("main.mor");
+    fs::write(&main, "LOAD_VAL 1\n.include \"lib.mor\"\nRETURN_VALUE\n").unwrap();
+
+    let result = ByteCode::transpile(main.to_str().unwrap());
     assert_eq!(
         result,
-        Err("Transpilation error at line 1: expected 1 argument, got 0".to_string())
+        Err(format!(
+            "Transpilation error at {}: undeclared variable y",
+            dir.path().join("lib.mor:2").display()
+        ))
     );
 }
 
 #[test]
-pub fn test_invalid_code_excessive_args() {
-    let mut file = NamedTempFile::new().expect("Failed to create temp file");
-    let code = "WRITE_VAR x x".to_string();
-    write!(file, "{}", code).expect("Failed to write to temp file");
+pub fn test_include_assert_location() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        dir.path().join("lib.mor"),
+        "\n\nLOAD_VAL 0 | ASSERT \"in lib\"\n",
+    )
+    .unwrap();
+    let main = dir.path().join("main.mor");
+    fs::write(&main, ".test\n.include \"lib.mor\"\nLOAD_VAL 6\n").unwrap();
 
-    let result = interpret(
-        file.path()
-            .to_str()
-            .expect("Failed to convert temp file path to string"),
+    let result = interpret(main.to_str().unwrap());
+    assert_eq!(
+        result,
+        Err(format!(
+            "Runtime error: unable to process current instruction, ip = 0x01: assertion failed at {}: in lib, stack: []",
+            dir.path().join("lib.mor:3").display()
+        ))
     );
+}
+
+#[test]
+pub fn test_include_cycle() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let main = dir.path().join("main.mor");
+    let other = dir.path().join("other.mor");
+    fs::write(&main, "LOAD_VAL 1\n.include \"other.mor\"\nRETURN_VALUE\n").unwrap();
+    fs::write(&other, "\n.include \"main.mor\"\n").unwrap();
+
+    let result = ByteCode::transpile(main.to_str().unwrap());
     assert_eq!(
         result,
-        Err("Transpilation error at line 1: expected 1 argument, got 2".to_string())
+        Err(format!(
+            "Transpilation error at {}:2: include cycle: {} -> {} -> {}",
+            other.display(),
+            main.display(),
+            other.display(),
+            main.display()
+        ))
+    );
+}
+
+#[test]
+pub fn test_include_dirs() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let lib = tempdir().expect("Failed to create temp dir");
+    fs::write(lib.path().join("consts.mor"), ".const ANSWER 42\n").unwrap();
+    let main = dir.path().join("main.mor");
+    fs::write(
+        &main,
+        ".include \"consts.mor\"\nLOAD_VAL ANSWER\nRETURN_VALUE\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        ByteCode::transpile(main.to_str().unwrap()),
+        Err("Transpilation error at line 1: included file consts.mor not found".to_string())
     );
+    let include_dirs = vec![PathBuf::from(lib.path())];
+    let bytecode = ByteCode::transpile_with_includes(main.to_str().unwrap(), &include_dirs);
+    assert!(bytecode.is_ok());
 }
 
 #[test]
-pub fn test_invalid_code_invalid_variable_name() {
+pub fn test_jump_and_goto_if_zero() {
+    /*
+     * if n == 0 { return 100 } else { return n * 2 }
+     */
+    let code = concat!(
+        ".param n\n",
+        "READ_VAR n\n",
+        "GOTO_IF_ZERO &zero\n",
+        "READ_VAR n | LOAD_VAL 2 | MULTIPLY\n",
+        "JUMP &end\n",
+        "&zero\n",
+        "LOAD_VAL 100\n",
+        "&end\n",
+        "RETURN_VALUE\n",
+    );
     let mut file = NamedTempFile::new().expect("Failed to create temp file");
-    let code = "WRITE_VAR .x".to_string();
     write!(file, "{}", code).expect("Failed to write to temp file");
+    let path = file.path().to_str().unwrap();
 
-    let result = interpret(
-        file.path()
-            .to_str()
-            .expect("Failed to convert temp file path to string"),
+    let result = interpret_with_args(path, &[("n".to_string(), 0)]);
+    assert_eq!(result, Ok(Some(100)));
+    let result = interpret_with_args(path, &[("n".to_string(), 21)]);
+    assert_eq!(result, Ok(Some(42)));
+}
+
+#[test]
+pub fn test_switch() {
+    /*
+     * match n { 0 => 10, 1 => 20, 2 => 30, _ => 0 }
+     */
+    let code = concat!(
+        ".param n\n",
+        "READ_VAR n\n",
+        "SWITCH &zero &one &two default &other\n",
+        "&zero | LOAD_VAL 10 | RETURN_VALUE\n",
+        "&one | LOAD_VAL 20 | RETURN_VALUE\n",
+        "&two | LOAD_VAL 30 | RETURN_VALUE\n",
+        "&other | LOAD_VAL 0 | RETURN_VALUE\n",
     );
+    let mut file = NamedTempFile::new().expect("Failed to create temp file");
+    write!(file, "{}", code).expect("Failed to write to temp file");
+    let path = file.path().to_str().unwrap();
+
+    for (n, expected) in [(0, 10), (1, 20), (2, 30), (3, 0), (1000, 0)] {
+        let result = interpret_with_args(path, &[("n".to_string(), n)]);
+        assert_eq!(result, Ok(Some(expected)));
+    }
+}
+
+#[test]
+pub fn test_scopes() {
+    /*
+     * sum of i * i for i in 1..=n, with the square kept in a scoped temporary
+     */
+    let code = concat!(
+        ".param n\n",
+        "LOAD_VAL 0 | WRITE_VAR sum | LOAD_VAL 0 | WRITE_VAR i\n",
+        "&loop\n",
+        "READ_VAR i | LOAD_VAL 1 | ADD | WRITE_VAR i\n",
+        ".scope\n",
+        "READ_VAR i | DUP | MULTIPLY | WRITE_VAR square\n",
+        "READ_VAR sum | READ_VAR square | ADD | WRITE_VAR sum\n",
+        ".endscope\n",
+        ".scope\n",
+        "READ_VAR i | READ_VAR n | TEST_GT | WRITE_VAR more\n",
+        "READ_VAR more\n",
+        ".endscope\n",
+        "GOTO &loop\n",
+        "READ_VAR sum\n",
+        "RETURN_VALUE\n",
+    );
+    let mut file = NamedTempFile::new().expect("Failed to create temp file");
+    write!(file, "{}", code).expect("Failed to write to temp file");
+    let path = file.path().to_str().unwrap();
+
+    assert_eq!(ByteCode::transpile(path).unwrap().get_data_size(), 4);
+    let result = interpret_with_args(path, &[("n".to_string(), 4)]);
+    assert_eq!(result, Ok(Some(30)));
+}
+
+fn interpreter_for(code: &str) -> Interpreter<'static> {
+    let mut reader = code.as_bytes();
+    Interpreter::new(ByteCode::transpile_reader(&mut reader).unwrap())
+}
+
+#[test]
+pub fn test_try_checked_overflow() {
+    let code = concat!(
+        "TRY &overflow\n",
+        "LOAD_VAL 300 | LOAD_VAL 300 | MULTIPLY\n",
+        "END_TRY\n",
+        "RETURN_VALUE\n",
+        "&overflow\n",
+        "LOAD_VAL 1000 | ADD\n",
+        "RETURN_VALUE\n",
+    );
+    let mut interpreter = interpreter_for(code);
+    interpreter.set_checked(true);
+    assert_eq!(interpreter.interpret(), Ok(Some(1002)));
+}
+
+#[test]
+pub fn test_try_invalid_argument() {
+    let code = concat!(
+        "TRY &invalid\n",
+        "LOAD_VAL 0 | RAND\n",
+        "END_TRY\n",
+        "RETURN_VALUE\n",
+        "&invalid\n",
+        "TRY &reversed\n",
+        "LOAD_VAL 4 | LOAD_VAL 5 | LOAD_VAL 3 | CLAMP\n",
+        "END_TRY\n",
+        "RETURN_VALUE\n",
+        "&reversed\n",
+        "ADD | RETURN_VALUE\n",
+    );
+    assert_eq!(interpreter_for(code).interpret(), Ok(Some(12)));
+}
+
+#[test]
+pub fn test_execution_report() {
+    let code = concat!(
+        "LOAD_VAL 20 | WRITE_VAR x\n",
+        "LOAD_VAL 1 | LOAD_VAL 2 | LOAD_VAL 3 | ADD\n",
+        "READ_VAR x | LOAD_VAL 2 | MULTIPLY | WRITE_VAR y\n",
+        "LOAD_VAL 7 | RETURN_VALUE\n",
+    );
+    let report = interpreter_for(code).run();
+    assert_eq!(report.result, Some(7));
+    assert_eq!(report.termination, Termination::Returned);
+    assert_eq!(report.stack, vec![1, 5]);
+    assert_eq!(report.vars["x"], 20);
+    assert_eq!(report.vars["y"], 40);
+    assert_eq!(report.steps, 12);
+    assert_eq!(report.max_stack_depth, 4);
+
+    let report = interpreter_for("LOAD_VAL 1 | LOAD_VAL 2").run();
+    assert_eq!(report.result, None);
+    assert_eq!(report.termination, Termination::FellOffEnd);
+    assert_eq!(report.stack, vec![1, 2]);
+
+    let report = interpreter_for("LOAD_VAL 1 | WRITE_VAR x | ADD").run();
     assert_eq!(
-        result,
-        Err("Transpilation error at line 1: invalid variable name .x".to_string())
+        report.termination,
+        Termination::Error(
+            "Runtime error: unable to process current instruction, ip = 0x02: no value on stack"
+                .to_string()
+        )
+    );
+    assert_eq!(report.vars["x"], 1);
+    assert_eq!(report.steps, 3);
+}
+
+#[test]
+pub fn test_vm_time_slices() {
+    let code = concat!(
+        "LOAD_VAL 0 | WRITE_VAR i\n",
+        "LOAD_VAL 1000 | WRITE_VAR limit\n",
+        "&loop\n",
+        "READ_VAR i | LOAD_VAL 1 | ADD | DUP | WRITE_VAR i\n",
+        "READ_VAR limit | TEST_GT | GOTO &loop\n",
+        "READ_VAR i | RETURN_VALUE\n",
+    );
+    let mut vm = Vm::from(interpreter_for(code));
+    assert_eq!(vm.run_for(50), Ok(true));
+    let i = vm.get_var("i").unwrap();
+    assert!(i > 0 && i < 10);
+    vm.set_var("limit", 20).unwrap();
+    while vm.run_for(50).unwrap() {}
+    assert_eq!(vm.termination(), Some(&Termination::Returned));
+    assert_eq!(vm.result(), Some(20));
+    assert!(vm.stack().is_empty());
+}
+
+#[test]
+pub fn test_host_functions() {
+    let code = concat!(
+        "LOAD_VAL 2 | CALL_HOST price\n",
+        "LOAD_VAL 3 | MULTIPLY | DUP | CALL_HOST metric\n",
+        "TRY &failed\n",
+        "LOAD_VAL 9 | CALL_HOST price\n",
+        "END_TRY\n",
+        "&failed\n",
+        "ADD | RETURN_VALUE\n",
+    );
+    let mut reader = code.as_bytes();
+    let bytecode = ByteCode::transpile_reader(&mut reader).unwrap();
+    let mut metrics = Vec::new();
+    let mut output = Vec::new();
+    let mut interpreter = Interpreter::with_io(bytecode.clone(), Io::new(&b""[..], &mut output));
+    interpreter.register_host("price", 1, |args| match args[0] {
+        1 => Ok(vec![25]),
+        2 => Ok(vec![40]),
+        item => Err(format!("no price for item {}", item)),
+    });
+    interpreter.register_host("metric", 1, |args| {
+        metrics.push(args[0]);
+        Ok(vec![])
+    });
+    assert_eq!(interpreter.interpret(), Ok(Some(125)));
+    drop(interpreter);
+    assert_eq!(metrics, vec![120]);
+
+    let mut interpreter = Interpreter::new(bytecode);
+    interpreter.register_host("price", 1, |_| Ok(vec![1]));
+    assert_eq!(
+        interpreter.interpret(),
+        Err("Verification error at ip = 0x05: unknown host function metric".to_string())
+    );
+    assert_eq!(interpreter.steps(), 0);
+}
+
+#[test]
+pub fn test_vm_snapshot_file() {
+    let code = concat!(
+        "LOAD_VAL 7\n",
+        "TRY &failed\n",
+        "LOAD_VAL 0 | WRITE_VAR i\n",
+        "&loop\n",
+        "READ_VAR i | LOAD_VAL 1 | ADD | DUP | WRITE_VAR i\n",
+        "LOAD_VAL 50 | TEST_GT | GOTO &loop\n",
+        "THROW\n",
+        "&failed\n",
+        "READ_VAR i | ADD | RETURN_VALUE\n",
+    );
+    let program = || {
+        let mut reader = code.as_bytes();
+        ByteCode::transpile_reader(&mut reader).unwrap()
+    };
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("state.snapshot");
+
+    let mut vm = Vm::new(program());
+    assert_eq!(vm.run_for(100), Ok(true));
+    fs::write(&path, vm.snapshot().unwrap()).unwrap();
+    drop(vm);
+
+    let mut vm = Vm::new(program());
+    vm.restore(&fs::read(&path).unwrap()).unwrap();
+    assert_eq!(vm.stack(), &[7]);
+    while vm.run_for(100).unwrap() {}
+    assert_eq!(vm.result(), Some(57));
+
+    let mut reader = "LOAD_VAL 1 | RETURN_VALUE".as_bytes();
+    let mut other = Vm::new(ByteCode::transpile_reader(&mut reader).unwrap());
+    assert_eq!(
+        other.restore(&fs::read(&path).unwrap()),
+        Err("Invalid snapshot: taken from a different program".to_string())
     );
 }